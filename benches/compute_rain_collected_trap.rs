@@ -3,8 +3,9 @@ use std::time::Duration;
 
 use criterion::{criterion_group, criterion_main, Criterion};
 use trap_rust::{
-    compute_rain_collected, compute_rain_collected3, compute_rain_collected_v,
-    trap, trap_unsafe, trap_v,
+    compute_rain_collected, compute_rain_collected3,
+    compute_rain_collected_parallel, compute_rain_collected_v, trap,
+    trap_simd, trap_unsafe, trap_v,
 };
 
 use trap_cpp::{trap_cpp, trap_cpp_dp};
@@ -20,6 +21,7 @@ fn bench_compute_rain_collected_trap(c: &mut Criterion) {
     });
     group.bench_function("trap", |b| b.iter(|| trap(&terrain)));
     group.bench_function("trap_unsafe", |b| b.iter(|| trap_unsafe(&terrain)));
+    group.bench_function("trap_simd", |b| b.iter(|| trap_simd(&terrain)));
     group.bench_function("trap_cpp", |b| b.iter(|| trap_cpp(&terrain)));
     group.bench_function("trap_cpp_dp", |b| b.iter(|| trap_cpp_dp(&terrain)));
     #[cfg(feature = "swift")]
@@ -46,6 +48,12 @@ fn bench_compute_rain_collected_trap(c: &mut Criterion) {
     group.bench_function("compute_rain_collected3", |b| {
         b.iter(|| compute_rain_collected3(&terrain))
     });
+    for threads in [1, 2, 4, 8] {
+        group.bench_function(
+            format!("compute_rain_collected_parallel_{}", threads),
+            |b| b.iter(|| compute_rain_collected_parallel(&terrain, threads)),
+        );
+    }
 
     group.finish();
 }
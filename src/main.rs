@@ -2,9 +2,13 @@
 //! Given a terrain represented by a sequence of integers, each
 //! representing the elevation of a spot, calculate the amount of
 //! rain that can be trapped in the terrain.
+//!
+//! Run without arguments to time the backends on a random terrain, or with
+//! the path of a file of little-endian `i64` elevations to compute the
+//! water capacity of the terrain stored in it.
 
 use trap_cpp::trap_cpp_dp;
-use trap_rust::compute_rain_collected;
+use trap_rust::{compute_rain_collected, compute_rain_collected_file};
 #[cfg(feature = "swift")]
 use trap_swift::trap_swift;
 use trap_zig::trap_zig_ffi;
@@ -14,6 +18,22 @@ const N: i64 = 100000;
 fn main() {
     use std::time::Instant;
 
+    if let Some(path) = std::env::args_os().nth(1) {
+        let start_time = Instant::now();
+        match compute_rain_collected_file(&path) {
+            Ok(water) => {
+                let execution_time = start_time.elapsed().as_millis();
+                println!("Execution time: {} milliseconds", execution_time);
+                println!("Water capacity: {}", water);
+            }
+            Err(error) => {
+                eprintln!("{}: {}", path.to_string_lossy(), error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let terrain: Vec<i64> = (0..N).map(|_| rand::random::<i64>() % N).collect();
 
     let start_time = Instant::now();
@@ -102,4 +102,13 @@ mod tests {
         let terrain11 = [0, -6, 0, -2, 8, -9, 0, 8, 9, -5];
         assert_eq!(trap_cpp(&terrain11), 33);
     }
+
+    #[test]
+    fn test_tiny_terrains() {
+        let terrains: [&[i64]; 5] = [&[], &[7], &[-3], &[1, 2], &[2, 1]];
+        for terrain in terrains {
+            assert_eq!(trap_cpp(terrain), 0);
+            assert_eq!(trap_cpp_dp(terrain), 0);
+        }
+    }
 }
@@ -44,6 +44,10 @@ extern "C" {
 
 
 unsigned long trap_cpp_dp(std::span<long> height) {
+    if (height.size() < 2)
+    {
+        return 0;
+    }
     size_t l = 0, r = height.size()-1;
     long level = std::numeric_limits<long>::min();
     unsigned long  water = 0;
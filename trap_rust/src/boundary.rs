@@ -0,0 +1,197 @@
+//! # Boundary conditions
+//! What lies beyond the ends of a terrain, and how it holds back the water
+//! trapped in it.
+
+/// What lies beyond an end of the terrain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Boundary {
+    /// Water flows off the end of the terrain, as if it dropped to
+    /// `i64::MIN`. This is the boundary of
+    /// [`compute_rain_collected`](crate::compute_rain_collected).
+    #[default]
+    Open,
+    /// A wall of the given height, water spills over it.
+    Wall(i64),
+    /// A lake with its surface at the given level.
+    ///
+    /// A lake holds back the water as a wall of the height of its surface:
+    /// the terrain next to it can't drain below that level, and water above
+    /// it flows into the lake. What the lake could flood is already filled
+    /// by the rain, as the terrain is filled to capacity, so `Lake(level)`
+    /// traps the same amount of rain as `Wall(level)`.
+    Lake(i64),
+}
+
+impl Boundary {
+    /// The level below which the boundary holds back the water.
+    pub fn level(&self) -> i64 {
+        match *self {
+            Boundary::Open => i64::MIN,
+            Boundary::Wall(height) => height,
+            Boundary::Lake(level) => level,
+        }
+    }
+}
+
+/// Compute the amount of rain trapped in the terrain between two
+/// boundaries.
+/// # Arguments
+/// * `height` - A slice of integers representing the elevation of the terrain.
+/// * `left` - What lies beyond the left end of the terrain.
+/// * `right` - What lies beyond the right end of the terrain.
+/// # Returns
+/// The amount of rain trapped in the terrain.
+/// # Example
+/// ```
+/// use trap_rust::{trap_with_boundaries, Boundary::*};
+/// let terrain = [3, 1, 2, 0];
+/// assert_eq!(trap_with_boundaries(&terrain, Open, Open), 1);
+/// assert_eq!(trap_with_boundaries(&terrain, Open, Wall(5)), 6);
+/// assert_eq!(trap_with_boundaries(&terrain, Lake(4), Wall(5)), 10);
+/// ```
+/// # Note
+/// A boundary acts like one more spot of its level beyond the end of the
+/// terrain. So the water surface on a spot is the lower of the maximum
+/// elevations on its left, including the left boundary, and on its right,
+/// including the right boundary, as in the two-pointer solution of
+/// [`trap`](crate::trap). A wall of `i64::MAX` closes the end of the
+/// terrain, two of them hold any amount of rain.
+pub fn trap_with_boundaries(
+    height: &[i64],
+    left: Boundary,
+    right: Boundary,
+) -> u64 {
+    let (mut l, mut r) = (0, height.len());
+    let (mut left_max, mut right_max) = (left.level(), right.level());
+    let mut trapped = 0u64;
+
+    while l < r {
+        let water = if left_max <= right_max {
+            left_max = left_max.max(height[l]);
+            l += 1;
+            left_max.wrapping_sub(height[l - 1])
+        } else {
+            r -= 1;
+            right_max = right_max.max(height[r]);
+            right_max.wrapping_sub(height[r])
+        };
+        // The difference always fits into a u64, even if it overflows i64.
+        trapped += water as u64;
+    }
+
+    trapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_rain_collected;
+    use crate::test_util::TERRAINS;
+
+    /// The water surfaces from the maxima on both sides, `O(n)` memory.
+    fn reference(height: &[i64], left: Boundary, right: Boundary) -> u64 {
+        let mut surface: Vec<i64> = height
+            .iter()
+            .scan(left.level(), |max, &h| {
+                *max = (*max).max(h);
+                Some(*max)
+            })
+            .collect();
+        let mut max = right.level();
+        for (s, &h) in surface.iter_mut().zip(height).rev() {
+            max = max.max(h);
+            *s = (*s).min(max);
+        }
+        surface
+            .iter()
+            .zip(height)
+            .map(|(&s, &h)| (s as i128 - h as i128) as u64)
+            .sum()
+    }
+
+    #[test]
+    fn test_trap_with_boundaries() {
+        let boundaries = [
+            Boundary::Open,
+            Boundary::Wall(-10),
+            Boundary::Wall(0),
+            Boundary::Wall(3),
+            Boundary::Wall(4),
+            Boundary::Wall(10),
+            Boundary::Lake(0),
+            Boundary::Lake(4),
+        ];
+        for terrain in TERRAINS {
+            assert_eq!(
+                trap_with_boundaries(terrain, Boundary::Open, Boundary::Open),
+                compute_rain_collected(terrain)
+            );
+            for left in boundaries {
+                for right in boundaries {
+                    assert_eq!(
+                        trap_with_boundaries(terrain, left, right),
+                        reference(terrain, left, right),
+                        "{:?} between {:?} and {:?}",
+                        terrain,
+                        left,
+                        right
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_trap_with_boundaries_closed() {
+        let terrain = [3, 1, 2, 0];
+        let wall = Boundary::Wall(i64::MAX);
+        // a closed end holds the water up to the other end
+        assert_eq!(trap_with_boundaries(&terrain, Boundary::Open, wall), 6);
+        assert_eq!(trap_with_boundaries(&terrain, wall, Boundary::Open), 1);
+        assert_eq!(trap_with_boundaries(&terrain, wall, Boundary::Lake(4)), 10);
+        // walls lower than the terrain change nothing
+        let low = Boundary::Wall(i64::MIN);
+        assert_eq!(trap_with_boundaries(&terrain, low, low), 1);
+        assert_eq!(Boundary::default(), Boundary::Open);
+    }
+
+    #[test]
+    fn test_trap_with_boundaries_lake() {
+        for terrain in TERRAINS {
+            for level in [i64::MIN, -3, 0, 2, 5, 10] {
+                let (lake, wall) =
+                    (Boundary::Lake(level), Boundary::Wall(level));
+                for other in [Boundary::Open, Boundary::Wall(3), lake] {
+                    assert_eq!(
+                        trap_with_boundaries(terrain, lake, other),
+                        trap_with_boundaries(terrain, wall, other)
+                    );
+                    assert_eq!(
+                        trap_with_boundaries(terrain, other, lake),
+                        trap_with_boundaries(terrain, other, wall)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_trap_with_boundaries_extreme() {
+        let terrains: [&[i64]; 3] = [
+            &[i64::MAX, i64::MIN, i64::MAX],
+            &[0, i64::MIN, 0, -1, 0],
+            &[i64::MIN, i64::MAX, i64::MIN],
+        ];
+        for terrain in terrains {
+            assert_eq!(
+                trap_with_boundaries(terrain, Boundary::Open, Boundary::Open),
+                compute_rain_collected(terrain)
+            );
+        }
+        let wall = Boundary::Wall(i64::MAX);
+        assert_eq!(trap_with_boundaries(&[i64::MIN], wall, wall), u64::MAX);
+        assert_eq!(trap_with_boundaries(&[i64::MIN], wall, Boundary::Open), 0);
+        let lake = Boundary::Lake(0);
+        assert_eq!(trap_with_boundaries(&[i64::MIN], wall, lake), 1 << 63);
+    }
+}
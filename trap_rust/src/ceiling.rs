@@ -0,0 +1,181 @@
+//! # Terrains with a ceiling
+//! Rain trapped in caves and culverts, where rock above each spot stops the
+//! water from rising.
+
+use crate::water_levels;
+
+/// Compute the amount of rain trapped in a terrain with a ceiling.
+/// # Arguments
+/// * `floor` - A slice of integers representing the elevation of the terrain.
+/// * `ceiling` - A slice of integers representing the elevation of the rock
+///   above each spot, `i64::MAX` where a spot is open to the sky.
+/// # Returns
+/// The amount of rain trapped between the floor and the ceiling.
+/// # Example
+/// ```
+/// use trap_rust::trap_with_ceiling;
+/// const SKY: i64 = i64::MAX;
+/// let floor = [5, 0, 0, 0, 5];
+/// // a culvert, the water passes beneath its roof
+/// assert_eq!(trap_with_ceiling(&floor, &[SKY, SKY, 2, SKY, SKY]), 12);
+/// // a cave, the air caught under its roof keeps the water out
+/// assert_eq!(trap_with_ceiling(&floor, &[SKY, 2, 4, 2, SKY]), 6);
+/// // without a ceiling this is the stair model
+/// assert_eq!(trap_with_ceiling(&floor, &[SKY; 5]), 15);
+/// ```
+/// # Note
+/// Enough rain reaches every spot to fill the terrain, which spills as in
+/// [`compute_rain_collected`](crate::compute_rain_collected):
+/// * Water passes beneath a ceiling into the next spots, so a low ceiling
+///   doesn't hold back water the way a wall of the floor does.
+/// * No water rises above the ceiling of its spot. The spot is full, but
+///   the water next to it may be higher.
+/// * Water under a ceiling rises only as long as the air above it can
+///   escape, along the spots, to the open sky or beyond the ends of the
+///   terrain. So it stops at the lowest ceiling the air has to pass on its
+///   easier way out, above which the air is caught in a pocket.
+///
+/// With no ceiling lower than the water surface of the stair model, the
+/// result is the same as of `compute_rain_collected`. As no rock can be
+/// higher than `i64::MAX`, a ceiling at `i64::MAX` is always open sky, and
+/// lets the air out.
+/// # Panics
+/// Panics if the slices differ in length, or if a ceiling is below its
+/// floor.
+pub fn trap_with_ceiling(floor: &[i64], ceiling: &[i64]) -> u64 {
+    assert_eq!(
+        floor.len(),
+        ceiling.len(),
+        "floor and ceiling differ in length"
+    );
+    assert!(
+        floor.iter().zip(ceiling).all(|(f, c)| f <= c),
+        "ceiling below floor"
+    );
+    let n = floor.len();
+    // The lowest ceiling the air above each spot passes on its way left.
+    let mut left = vec![i64::MAX; n];
+    let mut lowest = i64::MAX;
+    for i in 0..n {
+        lowest = if ceiling[i] == i64::MAX {
+            i64::MAX
+        } else {
+            lowest.min(ceiling[i])
+        };
+        left[i] = lowest;
+    }
+    let mut lowest = i64::MAX;
+    water_levels(floor)
+        .iter()
+        .zip(floor)
+        .enumerate()
+        .rev()
+        .map(|(i, (&surface, &h))| {
+            lowest = if ceiling[i] == i64::MAX {
+                i64::MAX
+            } else {
+                lowest.min(ceiling[i])
+            };
+            let air = left[i].max(lowest);
+            surface.min(air).max(h).wrapping_sub(h) as u64
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_rain_collected;
+    use crate::test_util::{random, TERRAINS};
+
+    /// The water surface of each spot from the maxima of the floor on both
+    /// sides, limited by the lowest ceiling the air above the spot passes
+    /// on its easier way out, `O(n^2)`.
+    fn reference(floor: &[i64], ceiling: &[i64]) -> u64 {
+        // The lowest ceiling on the way out through the spots, up to the
+        // first one open to the sky or beyond the end of the terrain.
+        let escape = |spots: &mut dyn Iterator<Item = &i64>| {
+            spots
+                .take_while(|&&c| c != i64::MAX)
+                .fold(i64::MAX, |lowest, &c| lowest.min(c))
+        };
+        (0..floor.len())
+            .map(|i| {
+                let left = floor[..=i].iter().max().unwrap();
+                let right = floor[i..].iter().max().unwrap();
+                let air = escape(&mut ceiling[..=i].iter().rev())
+                    .max(escape(&mut ceiling[i..].iter()));
+                let surface = (*left.min(right)).min(air).max(floor[i]);
+                (surface - floor[i]) as u64
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_trap_with_ceiling_unbounded() {
+        for terrain in TERRAINS {
+            let water = compute_rain_collected(terrain);
+            let sky = vec![i64::MAX; terrain.len()];
+            assert_eq!(trap_with_ceiling(terrain, &sky), water);
+            // a roof above every water surface changes nothing
+            let max = terrain.iter().copied().max().unwrap_or(0);
+            let roof = vec![max + 1; terrain.len()];
+            assert_eq!(trap_with_ceiling(terrain, &roof), water);
+            // a roof down to the floor keeps all the water out
+            assert_eq!(trap_with_ceiling(terrain, terrain), 0);
+        }
+    }
+
+    #[test]
+    fn test_trap_with_ceiling() {
+        const SKY: i64 = i64::MAX;
+        let floor = [5, 0, 0, 0, 5];
+        // the roof stops the water in its spot only
+        assert_eq!(trap_with_ceiling(&floor, &[SKY, 3, SKY, SKY, SKY]), 13);
+        // the air under the dome escapes through the open spot on the right
+        assert_eq!(trap_with_ceiling(&floor, &[SKY, 2, 4, 4, SKY]), 10);
+        // and under the roof of the ends beyond the ends of the terrain
+        assert_eq!(trap_with_ceiling(&[5, 0, 5], &[6, 3, 6]), 3);
+        assert_eq!(trap_with_ceiling(&[5, 0, 5], &[7, 8, 5]), 5);
+        // a ceiling at i64::MAX is open sky, the air escapes through it
+        assert_eq!(trap_with_ceiling(&floor, &[SKY, 2, 4, 2, SKY]), 6);
+        assert_eq!(trap_with_ceiling(&floor, &[SKY, 2, SKY, 2, SKY]), 9);
+        // walls of the floor still hold back the water
+        assert_eq!(
+            trap_with_ceiling(&[3, 0, 4, 0, 2], &[SKY, 1, SKY, 9, 9]),
+            3
+        );
+        // a roof down to the floor
+        assert_eq!(trap_with_ceiling(&floor, &[SKY, 0, 0, 0, SKY]), 0);
+    }
+
+    #[test]
+    fn test_trap_with_ceiling_random() {
+        let mut state = 0x1F83D9ABFB41BD6Bu64;
+        for _ in 0..1000 {
+            let n = random(&mut state) as usize % 30;
+            let floor: Vec<i64> =
+                (0..n).map(|_| (random(&mut state) % 10) as i64).collect();
+            let ceiling: Vec<i64> = floor
+                .iter()
+                .map(|&f| match random(&mut state) % 4 {
+                    0 => i64::MAX,
+                    r => f + (random(&mut state) % (4 * r)) as i64,
+                })
+                .collect();
+            let water = trap_with_ceiling(&floor, &ceiling);
+            assert_eq!(water, reference(&floor, &ceiling));
+            assert!(water <= compute_rain_collected(&floor));
+            // raising a ceiling never traps less water
+            let higher: Vec<i64> =
+                ceiling.iter().map(|&c| c.saturating_add(1)).collect();
+            assert!(trap_with_ceiling(&floor, &higher) >= water);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_trap_with_ceiling_below_floor() {
+        trap_with_ceiling(&[1, 2, 3], &[1, 1, i64::MAX]);
+    }
+}
@@ -0,0 +1,196 @@
+//! # Overflow-checked computation
+//! Variants of the algorithms that report an error instead of silently
+//! wrapping around when the amount of rain trapped doesn't fit into a
+//! `u64`, as for terrains mixing elevations close to `i64::MIN` and
+//! `i64::MAX`.
+
+use std::fmt;
+
+use crate::WaterDepths;
+
+/// Error returned by the overflow-checked algorithms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrapError {
+    /// The difference between the water level and the elevation of a
+    /// spot does not fit into a `u64`.
+    ///
+    /// The difference of two `i64` is at most `u64::MAX`, so this is never
+    /// returned for `i64` elevations. It is kept apart from
+    /// [`TrapError::VolumeOverflow`] for callers matching on the cause.
+    ElevationOverflow,
+    /// The total amount of rain trapped does not fit into a `u64`.
+    VolumeOverflow,
+}
+
+impl fmt::Display for TrapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrapError::ElevationOverflow => {
+                write!(f, "elevation difference overflows u64")
+            }
+            TrapError::VolumeOverflow => {
+                write!(f, "amount of rain trapped overflows u64")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TrapError {}
+
+/// Add the water collected on a spot to the water collected so far.
+fn collect(water: u64, stepsize: i64, x: i64) -> Result<u64, TrapError> {
+    // The depth always fits into a u64, even if it overflows i64.
+    let depth = stepsize.wrapping_sub(x) as u64;
+    water.checked_add(depth).ok_or(TrapError::VolumeOverflow)
+}
+
+/// Overflow-checked variant of compute_rain_collected.
+/// # Arguments
+/// * `height` - A slice of integers representing the elevation of the terrain.
+/// # Returns
+/// The amount of rain that can be trapped in the terrain, or a [`TrapError`]
+/// if an intermediate result overflows.
+/// # Example
+/// ```
+/// use trap_rust::{try_compute_rain_collected, TrapError};
+/// let terrain = [1, 4, 2, 5, 3, 6, 4, 7];
+/// assert_eq!(try_compute_rain_collected(&terrain), Ok(6));
+/// let terrain = [i64::MAX, i64::MIN, i64::MAX];
+/// assert_eq!(try_compute_rain_collected(&terrain), Ok(u64::MAX));
+/// let terrain = [i64::MAX, i64::MIN, 0, i64::MAX];
+/// assert_eq!(
+///     try_compute_rain_collected(&terrain),
+///     Err(TrapError::VolumeOverflow)
+/// );
+/// ```
+pub fn try_compute_rain_collected(height: &[i64]) -> Result<u64, TrapError> {
+    let mut steps = WaterDepths::new(height);
+    let mut water = 0u64;
+    while let Some((i, stepsize)) = steps.next_step() {
+        water = collect(water, stepsize, height[i])?;
+    }
+    Ok(water)
+}
+
+/// Overflow-checked variant of compute_rain_collected3.
+pub fn try_compute_rain_collected3(terrain: &[i64]) -> Result<u64, TrapError> {
+    let n = terrain.len();
+    if n < 3 {
+        return Ok(0);
+    }
+
+    let index_maximum = terrain
+        .iter()
+        .enumerate()
+        .max_by_key(|&(_, v)| v)
+        .unwrap() // since we ensured above that terrain isn't empty, this is safe
+        .0;
+
+    let (terrain_left_of_max_elevation, terrain_right_of_max_elevation) =
+        terrain.split_at(index_maximum);
+
+    let water_capacity_left = terrain_left_of_max_elevation
+        .iter()
+        .try_fold((i64::MIN, 0u64), |acc, &x| {
+            let stepsize = x.max(acc.0);
+            Ok::<_, TrapError>((stepsize, collect(acc.1, stepsize, x)?))
+        })?
+        .1;
+
+    let water_capacity_right = terrain_right_of_max_elevation
+        .iter()
+        .try_rfold((i64::MIN, 0u64), |acc, &x| {
+            let stepsize = x.max(acc.0);
+            Ok::<_, TrapError>((stepsize, collect(acc.1, stepsize, x)?))
+        })?
+        .1;
+
+    water_capacity_left
+        .checked_add(water_capacity_right)
+        .ok_or(TrapError::VolumeOverflow)
+}
+
+/// Overflow-checked variant of trap.
+pub fn try_trap(height: &[i64]) -> Result<u64, TrapError> {
+    let (mut left, mut right) = (0, height.len().saturating_sub(1));
+    let mut pool_height = i64::MIN;
+    let mut trapped = 0u64;
+
+    while left < right {
+        pool_height = pool_height.max(height[left].min(height[right]));
+        if height[left] <= height[right] {
+            trapped = collect(trapped, pool_height, height[left])?;
+            left += 1;
+        } else {
+            trapped = collect(trapped, pool_height, height[right])?;
+            right -= 1;
+        }
+    }
+
+    Ok(trapped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_rain_collected;
+    use crate::test_util::TERRAINS;
+
+    #[test]
+    fn test_checked_agrees() {
+        let extreme: &[i64] = &[i64::MIN, i64::MAX, i64::MIN];
+        for terrain in TERRAINS.into_iter().chain([extreme]) {
+            let water = Ok(compute_rain_collected(terrain));
+            assert_eq!(try_compute_rain_collected(terrain), water);
+            assert_eq!(try_compute_rain_collected3(terrain), water);
+            assert_eq!(try_trap(terrain), water);
+        }
+    }
+
+    #[test]
+    fn test_large_depths() {
+        // depths overflowing i64 still fit into a u64
+        let terrains: [(&[i64], u64); 3] = [
+            (&[i64::MAX, i64::MIN, i64::MAX], u64::MAX),
+            (&[0, i64::MAX, -1, i64::MAX, 0], 1 << 63),
+            (&[i64::MAX, 3, -2, i64::MAX], 18446744073709551613),
+        ];
+        for (terrain, water) in terrains {
+            assert_eq!(compute_rain_collected(terrain), water);
+            assert_eq!(try_compute_rain_collected(terrain), Ok(water));
+            assert_eq!(try_compute_rain_collected3(terrain), Ok(water));
+            assert_eq!(try_trap(terrain), Ok(water));
+        }
+    }
+
+    #[test]
+    fn test_volume_overflow() {
+        let error = Err(TrapError::VolumeOverflow);
+        for terrain in [
+            [i64::MAX, 0, 0, 0, i64::MAX],
+            [i64::MAX, i64::MIN, 0, 0, i64::MAX],
+        ] {
+            assert_eq!(try_compute_rain_collected(&terrain), error);
+            assert_eq!(try_compute_rain_collected3(&terrain), error);
+            assert_eq!(try_trap(&terrain), error);
+        }
+
+        // i64::MAX + i64::MAX + 1 == u64::MAX still fits
+        let terrain = [i64::MAX, 0, 0, i64::MAX, 0, 1, 0];
+        assert_eq!(try_compute_rain_collected(&terrain), Ok(u64::MAX));
+        assert_eq!(try_compute_rain_collected3(&terrain), Ok(u64::MAX));
+        assert_eq!(try_trap(&terrain), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            TrapError::ElevationOverflow.to_string(),
+            "elevation difference overflows u64"
+        );
+        assert_eq!(
+            TrapError::VolumeOverflow.to_string(),
+            "amount of rain trapped overflows u64"
+        );
+    }
+}
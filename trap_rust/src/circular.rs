@@ -0,0 +1,149 @@
+//! # Circular terrains
+//! Rain trapped in a terrain wrapping around, like a circular track or an
+//! annular tank, where the last spot neighbours the first one.
+
+use crate::{compute_rain_collected, compute_rain_collected_iter};
+
+/// Compute the amount of rain trapped in a circular terrain draining
+/// through its lowest spots.
+/// # Arguments
+/// * `height` - A slice of integers representing the elevation of the
+///   terrain, the last spot next to the first one.
+/// # Returns
+/// The amount of rain trapped in the terrain.
+/// # Example
+/// ```
+/// use trap_rust::trap_circular;
+/// assert_eq!(trap_circular(&[0, 3, 1, 2]), 1);
+/// assert_eq!(trap_circular(&[1, 2, 0, 3]), 1);
+/// assert_eq!(trap_circular(&[3, 1, 3, 1]), 0);
+/// ```
+/// # Note
+/// A ring has no ends for the water to flow off, so it would hold any
+/// amount of rain. Instead, every spot of the lowest elevation is an outlet
+/// where the water is lost, as at the ends of
+/// [`compute_rain_collected`](crate::compute_rain_collected). The outlets
+/// split the ring into stretches with open ends, the one from the last
+/// outlet to the first wrapping around. So the result doesn't depend on
+/// where the ring starts.
+pub fn trap_circular(height: &[i64]) -> u64 {
+    let Some(&lowest) = height.iter().min() else {
+        return 0;
+    };
+    // the terrain isn't empty, so there is an outlet
+    let first = height.iter().position(|&h| h == lowest).unwrap();
+    let last = height.iter().rposition(|&h| h == lowest).unwrap();
+    let inner: u64 = height[first + 1..=last]
+        .split(|&h| h == lowest)
+        .map(compute_rain_collected)
+        .sum();
+    inner
+        + compute_rain_collected_iter(
+            height[last + 1..].iter().chain(&height[..first]).copied(),
+        )
+}
+
+/// Compute the amount of rain trapped in a circular terrain draining
+/// through a given spot.
+/// # Arguments
+/// * `height` - A slice of integers representing the elevation of the
+///   terrain, the last spot next to the first one.
+/// * `outlet` - The index of the spot where the water is lost.
+/// # Returns
+/// The amount of rain trapped in the terrain.
+/// # Example
+/// ```
+/// use trap_rust::trap_circular_with_outlet;
+/// assert_eq!(trap_circular_with_outlet(&[0, 3, 1, 2], 0), 1);
+/// assert_eq!(trap_circular_with_outlet(&[0, 3, 1, 2], 2), 2);
+/// ```
+/// # Note
+/// The ring cut open at the outlet is a terrain with open ends, running
+/// from the spot after the outlet around to the spot before it.
+/// # Panics
+/// Panics if `outlet` is out of bounds.
+pub fn trap_circular_with_outlet(height: &[i64], outlet: usize) -> u64 {
+    assert!(
+        outlet < height.len(),
+        "index {} out of bounds for terrain of length {}",
+        outlet,
+        height.len()
+    );
+    compute_rain_collected_iter(
+        height[outlet + 1..]
+            .iter()
+            .chain(&height[..outlet])
+            .copied(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random, TERRAINS};
+    use crate::trap_with_drains;
+
+    /// The ring cut open at its first lowest spot, with drains at all the
+    /// lowest spots.
+    fn reference(height: &[i64]) -> u64 {
+        let Some(&lowest) = height.iter().min() else {
+            return 0;
+        };
+        let first = height.iter().position(|&h| h == lowest).unwrap();
+        let mut ring = height.to_vec();
+        ring.rotate_left(first);
+        let spots: Vec<Option<i64>> =
+            ring.iter().map(|&h| (h != lowest).then_some(h)).collect();
+        trap_with_drains(&spots)
+    }
+
+    fn check_rotations(terrain: &[i64]) {
+        let water = trap_circular(terrain);
+        assert_eq!(water, reference(terrain), "{:?}", terrain);
+        let n = terrain.len();
+        let mut rotated = terrain.to_vec();
+        for k in 0..n {
+            assert_eq!(trap_circular(&rotated), water, "{:?}", rotated);
+            for outlet in 0..n {
+                assert_eq!(
+                    trap_circular_with_outlet(&rotated, outlet),
+                    trap_circular_with_outlet(terrain, (outlet + k) % n),
+                );
+            }
+            rotated.rotate_left(1);
+        }
+        // mirrored, the ring runs the other way around
+        let mirrored: Vec<i64> = terrain.iter().rev().copied().collect();
+        assert_eq!(trap_circular(&mirrored), water);
+    }
+
+    #[test]
+    fn test_trap_circular() {
+        for terrain in TERRAINS {
+            check_rotations(terrain);
+        }
+        // the water wraps around from the last spot to the first one
+        assert_eq!(trap_circular(&[2, 4, 0, 4, 1]), 5);
+        // with a single lowest spot, it is the outlet
+        let terrain = [4, 2, 0, 3, 2, 5];
+        assert_eq!(trap_circular(&terrain), 1);
+        assert_eq!(trap_circular_with_outlet(&terrain, 2), 1);
+    }
+
+    #[test]
+    fn test_trap_circular_random() {
+        let mut state = 0x428A2F98D728AE22u64;
+        for _ in 0..300 {
+            let n = random(&mut state) as usize % 20 + 1;
+            let terrain: Vec<i64> =
+                (0..n).map(|_| (random(&mut state) % 10) as i64).collect();
+            check_rotations(&terrain);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_trap_circular_outlet_out_of_bounds() {
+        trap_circular_with_outlet(&[1, 2, 3], 3);
+    }
+}
@@ -0,0 +1,303 @@
+//! # Fill curves
+//! How the water stored in a terrain grows with the amount of rain falling
+//! evenly on all of its spots, from no rain up to saturation.
+
+use crate::rainfall::flows_left;
+
+/// The water stored in a terrain as a piecewise-linear function of the
+/// total amount of rain falling evenly on it.
+///
+/// Rain falling on the terrain flows and spills as in
+/// [`simulate_rain`](crate::simulate_rain), except that water isn't split
+/// into units. Once every basin is full, all further rain flows off the
+/// ends of the terrain and the stored volume is the amount of rain trapped
+/// in the terrain.
+/// # Example
+/// ```
+/// use trap_rust::fill_curve;
+/// let curve = fill_curve(&[3, 0, 3]);
+/// // the rain on the walls flows off the ends of the terrain
+/// assert_eq!(curve.volume_at(6.0), 2.0);
+/// assert_eq!(curve.volume_at(100.0), 3.0);
+/// assert_eq!(curve.rain_needed_for(3.0), Some(9.0));
+/// assert_eq!(curve.rain_needed_for(4.0), None);
+/// assert_eq!(curve.rain_needed_for(-1.0), None);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FillCurve {
+    // (total rain, stored volume), the last one reaching saturation
+    breakpoints: Vec<(f64, f64)>,
+}
+
+impl FillCurve {
+    /// The points `(total rain, stored volume)` at which the slope of the
+    /// curve changes, starting at `(0, 0)` and ending where the terrain is
+    /// saturated.
+    pub fn breakpoints(&self) -> &[(f64, f64)] {
+        &self.breakpoints
+    }
+
+    /// The volume of water stored once the terrain is saturated.
+    pub fn capacity(&self) -> f64 {
+        self.breakpoints.last().unwrap().1
+    }
+
+    fn segment_start(&self, i: usize) -> Option<&(f64, f64)> {
+        i.checked_sub(1).and_then(|i| self.breakpoints.get(i))
+    }
+
+    /// The volume of water stored after `rain` units of rain have fallen
+    /// evenly on the terrain.
+    pub fn volume_at(&self, rain: f64) -> f64 {
+        let i = self.breakpoints.partition_point(|&(x, _)| x <= rain);
+        match (self.segment_start(i), self.breakpoints.get(i)) {
+            (Some(&(x0, y0)), Some(&(x1, y1))) => {
+                y0 + (y1 - y0) * (rain - x0) / (x1 - x0)
+            }
+            (Some(_), None) => self.capacity(),
+            _ => 0.0,
+        }
+    }
+
+    /// The units of rain that have to fall evenly on the terrain to store
+    /// `volume` units of water, or `None` if the terrain can't hold that
+    /// much, or if `volume` is negative or `NaN`.
+    pub fn rain_needed_for(&self, volume: f64) -> Option<f64> {
+        if !(0.0..=self.capacity()).contains(&volume) {
+            return None;
+        }
+        let i = self.breakpoints.partition_point(|&(_, y)| y < volume);
+        match (self.segment_start(i), self.breakpoints.get(i)) {
+            (Some(&(x0, y0)), Some(&(x1, y1))) => {
+                Some(x0 + (x1 - x0) * (volume - y0) / (y1 - y0))
+            }
+            _ => Some(0.0),
+        }
+    }
+}
+
+/// Compute the fill curve of the terrain.
+/// # Arguments
+/// * `height` - A slice of integers representing the elevation of the terrain.
+/// # Returns
+/// The volume of water stored as a function of the total rain.
+/// # Note
+/// As the terrain splits at its maximum elevation, see
+/// [`simulate_rain`](crate::simulate_rain), the water stored is all the
+/// rain fallen minus the water that flowed off the ends of the terrain.
+/// Left of the maximum, the basins between the steps of the stair going up
+/// from the left end spill, once full, over their left step into the next
+/// basin. So the water flowing off the left end is found by following the
+/// stair down from the maximum, each basin passing on what it receives
+/// once it received as much as it holds. Every flow is a sum of hinge
+/// functions of the rain falling on each spot, and each basin merges the
+/// hinges before the time it is full into a single one. The same holds for
+/// the right of the maximum, so the curve is computed in `O(n)`.
+pub fn fill_curve(height: &[i64]) -> FillCurve {
+    let n = height.len();
+    let mut breakpoints = vec![(0.0, 0.0)];
+    if n == 0 {
+        return FillCurve { breakpoints };
+    }
+    let k = height
+        .iter()
+        .enumerate()
+        .rev() // max_by_key picks the last maximum
+        .max_by_key(|&(_, h)| h)
+        .unwrap() // terrain isn't empty
+        .0;
+
+    // Outflow off the left end, following the strict prefix maxima down
+    // from the leftmost maximum.
+    let records: Vec<usize> = (0..k)
+        .scan(i64::MIN, |max, i| {
+            let record = height[i] > *max;
+            *max = (*max).max(height[i]);
+            Some((i, record))
+        })
+        .filter_map(|(i, record)| record.then_some(i))
+        .collect();
+    let mut left = Flow::default();
+    let mut right_wall = k;
+    left.linear += flows_left(height, k) as u8 as f64;
+    for &wall in records.iter().rev() {
+        let to_left = flows_left(height, wall);
+        left.linear += !to_left as u8 as f64;
+        left.spill(height, wall + 1, right_wall, height[wall]);
+        left.linear += to_left as u8 as f64;
+        right_wall = wall;
+    }
+
+    // Outflow off the right end, following the strict suffix maxima down
+    // from the rightmost maximum right of k.
+    let mut records: Vec<usize> = Vec::new();
+    for i in (k + 1..n).rev() {
+        if records.last().is_none_or(|&j| height[i] > height[j]) {
+            records.push(i);
+        }
+    }
+    let mut right = Flow::default();
+    let mut left_wall = k;
+    right.linear += !flows_left(height, k) as u8 as f64;
+    for &wall in records.iter().rev() {
+        let to_left = flows_left(height, wall);
+        right.linear += to_left as u8 as f64;
+        right.spill(height, left_wall + 1, wall, height[wall]);
+        right.linear += !to_left as u8 as f64;
+        left_wall = wall;
+    }
+
+    // stored(t) = n * t - left(t) - right(t) for rain t on each spot
+    // Both flows keep their hinges latest first, merge them earliest first.
+    let (mut from_left, mut from_right) = (left.hinges, right.hinges);
+    let mut hinges = Vec::with_capacity(from_left.len() + from_right.len());
+    while let Some(hinge) = match (from_left.last(), from_right.last()) {
+        (Some(l), Some(r)) if r.0 < l.0 => from_right.pop(),
+        (Some(_), _) => from_left.pop(),
+        (None, _) => from_right.pop(),
+    } {
+        hinges.push(hinge);
+    }
+    debug_assert!(hinges.windows(2).all(|w| w[0].0 <= w[1].0));
+    let mut slope = n as f64 - left.linear - right.linear;
+    let (mut t, mut volume) = (0.0, 0.0);
+    for (at, change) in hinges {
+        volume += slope * (at - t);
+        slope -= change;
+        t = at;
+        match breakpoints.last_mut() {
+            Some(last) if last.0 == n as f64 * t => last.1 = volume,
+            _ => breakpoints.push((n as f64 * t, volume)),
+        }
+    }
+    // the last basin filled up, avoid rounding errors on the capacity
+    breakpoints.last_mut().unwrap().1 =
+        crate::compute_rain_collected(height) as f64;
+    FillCurve { breakpoints }
+}
+
+/// A flow of water as a function of the rain `t` falling on each spot,
+/// `linear * t` plus a hinge `slope * max(0, t - at)` for each
+/// `(at, slope)` of `hinges`.
+#[derive(Debug, Default)]
+struct Flow {
+    linear: f64,
+    hinges: Vec<(f64, f64)>, // latest first
+}
+
+impl Flow {
+    /// Let the flow and the rain on the spots `l..r` fill the basin of these
+    /// spots up to `level`, and continue with what spills over.
+    fn spill(&mut self, height: &[i64], l: usize, r: usize, level: i64) {
+        let capacity: i128 = height[l..r]
+            .iter()
+            .map(|&h| level as i128 - h as i128)
+            .sum();
+        let capacity = capacity as f64;
+        let (mut t, mut water) = (0.0, 0.0);
+        let mut slope = self.linear + (r - l) as f64;
+        loop {
+            let full = if water >= capacity {
+                t
+            } else if slope > 0.0 {
+                t + (capacity - water) / slope
+            } else {
+                f64::INFINITY
+            };
+            match self.hinges.last() {
+                Some(&(at, change)) if at <= full => {
+                    water += slope * (at - t);
+                    slope += change;
+                    t = at;
+                    self.hinges.pop();
+                }
+                _ => {
+                    if full.is_finite() && slope > 0.0 {
+                        self.hinges.push((full, slope));
+                    }
+                    self.linear = 0.0;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random, TERRAINS};
+    use crate::{compute_rain_collected, simulate_rain};
+
+    fn check(terrain: &[i64]) {
+        let curve = fill_curve(terrain);
+        assert_eq!(curve.capacity(), compute_rain_collected(terrain) as f64);
+        assert_eq!(curve.breakpoints()[0], (0.0, 0.0));
+        // saturation is reached at the last breakpoint
+        let (saturation, _) = *curve.breakpoints().last().unwrap();
+        assert_eq!(curve.volume_at(saturation * 2.0 + 1.0), curve.capacity());
+
+        let n = terrain.len();
+        for rain in 0..20 {
+            let total = (rain * n) as f64;
+            let stored: i64 =
+                simulate_rain(terrain, &vec![rain as u64; n]).iter().sum();
+            let volume = curve.volume_at(total);
+            assert!(
+                (volume - stored as f64).abs() < 1e-6,
+                "{} rain on {:?}: {} instead of {}",
+                rain,
+                terrain,
+                volume,
+                stored
+            );
+            if volume > 0.0 && volume < curve.capacity() {
+                let needed = curve.rain_needed_for(volume).unwrap();
+                assert!((needed - total).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fill_curve() {
+        for terrain in TERRAINS {
+            check(terrain);
+        }
+    }
+
+    #[test]
+    fn test_fill_curve_random() {
+        let mut state = 0x9B05688C2B3E6C1Fu64;
+        for _ in 0..300 {
+            let n = random(&mut state) as usize % 30 + 1;
+            let terrain: Vec<i64> =
+                (0..n).map(|_| (random(&mut state) % 20) as i64).collect();
+            check(&terrain);
+        }
+    }
+
+    #[test]
+    fn test_fill_curve_concave() {
+        let curve = fill_curve(&[9, 0, 5, 0, 4, 8, 1, 1, 1, 7, 3]);
+        let slopes: Vec<f64> = curve
+            .breakpoints()
+            .windows(2)
+            .map(|w| (w[1].1 - w[0].1) / (w[1].0 - w[0].0))
+            .collect();
+        assert!(slopes.windows(2).all(|w| w[1] < w[0]));
+        assert_eq!(curve.rain_needed_for(0.0), Some(0.0));
+    }
+
+    #[test]
+    fn test_rain_needed_for_invalid() {
+        for terrain in [&[][..], &[3, 0, 3], &[9, 0, 5, 0, 4, 8, 1, 1, 1, 7]] {
+            let curve = fill_curve(terrain);
+            assert_eq!(curve.rain_needed_for(-1.0), None);
+            assert_eq!(curve.rain_needed_for(-f64::MIN_POSITIVE), None);
+            assert_eq!(curve.rain_needed_for(f64::NEG_INFINITY), None);
+            assert_eq!(curve.rain_needed_for(f64::NAN), None);
+            assert_eq!(curve.rain_needed_for(f64::INFINITY), None);
+            assert_eq!(curve.rain_needed_for(0.0), Some(0.0));
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! # Terrains with drains
+//! Rain trapped in a terrain with bottomless holes, where all the water
+//! reaching them is lost.
+
+/// Compute the amount of rain trapped in a terrain with drains.
+/// # Arguments
+/// * `height` - A slice of the elevations of the terrain, `None` for a
+///   drain.
+/// # Returns
+/// The amount of rain trapped in the terrain.
+/// # Example
+/// ```
+/// use trap_rust::trap_with_drains;
+/// let terrain = [Some(3), Some(0), Some(2), Some(4)];
+/// assert_eq!(trap_with_drains(&terrain), 4);
+/// // the pool drains down to the lowest barrier between it and the drain
+/// let terrain = [Some(3), Some(0), Some(2), None, Some(4)];
+/// assert_eq!(trap_with_drains(&terrain), 2);
+/// let terrain = [Some(3), Some(0), None, Some(2), Some(4)];
+/// assert_eq!(trap_with_drains(&terrain), 0);
+/// ```
+/// # Note
+/// A drain holds no water, and water flows into it as it flows off the
+/// ends of the terrain. So the drains split the terrain into stretches
+/// with open ends, and the water trapped in each stretch is found with
+/// the two-pointer solution of [`trap`](crate::trap). Without drains, the
+/// result is the same as of
+/// [`compute_rain_collected`](crate::compute_rain_collected).
+pub fn trap_with_drains(height: &[Option<i64>]) -> u64 {
+    // The elevations of each stretch, in a buffer shared by all of them.
+    let mut stretch = Vec::new();
+    height
+        .split(Option::is_none)
+        .map(|spots| {
+            stretch.clear();
+            stretch.extend(
+                spots.iter().map(|h| h.expect("stretches have no drains")),
+            );
+            crate::generic::trap(&stretch)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_rain_collected;
+    use crate::test_util::{random, TERRAINS};
+
+    /// The water surface of each spot from the barriers between it and
+    /// the nearest drain or end on each side, `O(n^2)`.
+    fn reference(height: &[Option<i64>]) -> u64 {
+        let barrier = |spots: &mut dyn Iterator<Item = &Option<i64>>| {
+            spots.map_while(|&h| h).max().unwrap()
+        };
+        (0..height.len())
+            .filter_map(|i| {
+                let h = height[i]?;
+                let left = barrier(&mut height[..=i].iter().rev());
+                let right = barrier(&mut height[i..].iter());
+                Some((left.min(right) - h) as u64)
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_trap_with_drains() {
+        for terrain in TERRAINS {
+            let spots: Vec<Option<i64>> =
+                terrain.iter().copied().map(Some).collect();
+            assert_eq!(
+                trap_with_drains(&spots),
+                compute_rain_collected(terrain)
+            );
+            // a drain on each spot in turn
+            for i in 0..terrain.len() {
+                let mut drained = spots.clone();
+                drained[i] = None;
+                assert_eq!(trap_with_drains(&drained), reference(&drained));
+            }
+        }
+        assert_eq!(trap_with_drains(&[None]), 0);
+        assert_eq!(trap_with_drains(&[None, None, Some(1)]), 0);
+        let extreme = [Some(i64::MAX), Some(i64::MIN), Some(i64::MAX), None];
+        assert_eq!(trap_with_drains(&extreme), u64::MAX);
+    }
+
+    #[test]
+    fn test_trap_with_drains_random() {
+        let mut state = 0x5BE0CD19137E2179u64;
+        for _ in 0..1000 {
+            let n = random(&mut state) as usize % 30;
+            let terrain: Vec<Option<i64>> = (0..n)
+                .map(|_| match random(&mut state) % 10 {
+                    0 => None,
+                    h => Some(h as i64),
+                })
+                .collect();
+            assert_eq!(trap_with_drains(&terrain), reference(&terrain));
+        }
+    }
+}
@@ -0,0 +1,446 @@
+//! # Dynamic terrains
+//! A terrain that can be edited spot by spot while keeping the amount of
+//! rain trapped in it cheap to query.
+
+/// Smallest number of spots of a block of a [`DynamicTerrain`].
+const MIN_BLOCK_SIZE: usize = 64;
+
+/// A terrain supporting point updates, insertions and removals.
+///
+/// The water collected on a spot is the lower of the maximum elevation on
+/// its left and on its right (both including the spot itself) minus its
+/// elevation. One of those maxima always is the global maximum `M`, so the
+/// amount of rain trapped is `ΣP + ΣS - n * M - Σh`, where `P` are the
+/// prefix maxima and `S` the suffix maxima of the terrain.
+///
+/// The terrain is split into blocks of about `B = √n` spots. Each block
+/// keeps the prefix and suffix maxima of its own spots, so the sum of the
+/// prefix maxima of the whole terrain is found by a binary search per block
+/// for the spots that are lower than the maximum of all blocks before it.
+/// Blocks growing beyond `2B` spots are split, and blocks shrinking below
+/// `B / 2` spots are merged with a neighbour. Once `√n` is off from `B` by
+/// more than a factor of two, all blocks are rebuilt for the new size. So
+/// an edit finds its block by a binary search on the first index of each
+/// block and rebuilds it in `O(√n)` amortized, and
+/// [`DynamicTerrain::total_water`] takes `O(√n * log n)`.
+/// # Example
+/// ```
+/// use trap_rust::DynamicTerrain;
+/// let mut terrain = DynamicTerrain::new(&[1, 4, 2, 5, 3, 6, 4, 7]);
+/// assert_eq!(terrain.total_water(), 6);
+/// terrain.set(2, 0);
+/// assert_eq!(terrain.total_water(), 8);
+/// terrain.insert(0, 9);
+/// assert_eq!(terrain.total_water(), 26);
+/// assert_eq!(terrain.remove(8), 7);
+/// assert_eq!(terrain.total_water(), 17);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DynamicTerrain {
+    blocks: Vec<Block>,
+    // starts[b]: index of the first spot of block b, followed by len
+    starts: Vec<usize>,
+    min_block_size: usize,
+    block_size: usize,
+    len: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Block {
+    height: Vec<i64>,
+    // prefix_max[k]: maximum of height[..=k], non-decreasing
+    prefix_max: Vec<i64>,
+    // prefix_tail[k]: sum of prefix_max[k..]
+    prefix_tail: Vec<i128>,
+    // suffix_max[k]: maximum of height[k..], non-increasing
+    suffix_max: Vec<i64>,
+    // suffix_head[k]: sum of suffix_max[..k]
+    suffix_head: Vec<i128>,
+    sum: i128,
+}
+
+impl Block {
+    fn new(height: Vec<i64>) -> Self {
+        let mut block = Block {
+            height,
+            ..Block::default()
+        };
+        block.rebuild();
+        block
+    }
+
+    fn rebuild(&mut self) {
+        let n = self.height.len();
+        self.prefix_max.clear();
+        self.prefix_max
+            .extend(self.height.iter().scan(i64::MIN, |m, &x| {
+                *m = x.max(*m);
+                Some(*m)
+            }));
+        self.prefix_tail.clear();
+        self.prefix_tail.resize(n + 1, 0);
+        for k in (0..n).rev() {
+            self.prefix_tail[k] =
+                self.prefix_tail[k + 1] + self.prefix_max[k] as i128;
+        }
+
+        self.suffix_max.clear();
+        self.suffix_max.resize(n, 0);
+        let mut m = i64::MIN;
+        for k in (0..n).rev() {
+            m = m.max(self.height[k]);
+            self.suffix_max[k] = m;
+        }
+        self.suffix_head.clear();
+        self.suffix_head.resize(n + 1, 0);
+        for k in 0..n {
+            self.suffix_head[k + 1] =
+                self.suffix_head[k] + self.suffix_max[k] as i128;
+        }
+
+        self.sum = self.height.iter().map(|&x| x as i128).sum();
+    }
+
+    fn max(&self) -> i64 {
+        self.prefix_max.last().copied().unwrap_or(i64::MIN)
+    }
+
+    /// Sum of the prefix maxima of the block's spots, given the maximum of
+    /// all spots left of the block.
+    fn prefix_max_sum(&self, before: i64) -> i128 {
+        let lower = self.prefix_max.partition_point(|&p| p <= before);
+        before as i128 * lower as i128 + self.prefix_tail[lower]
+    }
+
+    /// Sum of the suffix maxima of the block's spots, given the maximum of
+    /// all spots right of the block.
+    fn suffix_max_sum(&self, after: i64) -> i128 {
+        let higher = self.suffix_max.partition_point(|&s| s > after);
+        self.suffix_head[higher]
+            + after as i128 * (self.height.len() - higher) as i128
+    }
+}
+
+impl DynamicTerrain {
+    /// Create a dynamic terrain from the given elevations.
+    pub fn new(height: &[i64]) -> Self {
+        Self::with_block_size(height, MIN_BLOCK_SIZE)
+    }
+
+    fn with_block_size(height: &[i64], min_block_size: usize) -> Self {
+        let block_size = min_block_size.max(height.len().isqrt());
+        let mut blocks: Vec<Block> = height
+            .chunks(block_size)
+            .map(|chunk| Block::new(chunk.to_vec()))
+            .collect();
+        // the last chunk may be short, then it joins the one before it
+        if blocks.len() > 1
+            && blocks.last().unwrap().height.len() < block_size / 2
+        {
+            let last = blocks.pop().unwrap();
+            let block = blocks.last_mut().unwrap();
+            block.height.extend(last.height);
+            block.rebuild();
+        }
+        let mut terrain = DynamicTerrain {
+            blocks,
+            starts: Vec::new(),
+            min_block_size,
+            block_size,
+            len: height.len(),
+        };
+        terrain.starts = std::iter::once(0)
+            .chain(terrain.blocks.iter().scan(0, |start, block| {
+                *start += block.height.len();
+                Some(*start)
+            }))
+            .collect();
+        terrain
+    }
+
+    /// The number of spots of the terrain.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the terrain has no spots.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The elevation of the spot at `index`, if any.
+    pub fn get(&self, index: usize) -> Option<i64> {
+        if index >= self.len {
+            return None;
+        }
+        let (b, k) = self.locate(index);
+        Some(self.blocks[b].height[k])
+    }
+
+    /// The elevations of the terrain.
+    pub fn to_vec(&self) -> Vec<i64> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.height.iter().copied())
+            .collect()
+    }
+
+    /// Change the elevation of the spot at `index`.
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: usize, h: i64) {
+        self.check_index(index, self.len);
+        let (b, k) = self.locate(index);
+        self.blocks[b].height[k] = h;
+        self.blocks[b].rebuild();
+    }
+
+    /// Insert a spot of elevation `h` at `index`, shifting all spots after
+    /// it to the right.
+    /// # Panics
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, h: i64) {
+        self.check_index(index, self.len + 1);
+        if self.blocks.is_empty() {
+            self.blocks.push(Block::default());
+            self.starts.push(0);
+        }
+        let (b, k) = if index == self.len {
+            let b = self.blocks.len() - 1;
+            (b, self.blocks[b].height.len())
+        } else {
+            self.locate(index)
+        };
+        self.blocks[b].height.insert(k, h);
+        self.len += 1;
+        self.starts[b + 1..]
+            .iter_mut()
+            .for_each(|start| *start += 1);
+        if self.blocks[b].height.len() > 2 * self.block_size {
+            self.split(b);
+        }
+        self.blocks[b].rebuild();
+        self.resize();
+    }
+
+    /// Remove the spot at `index` and return its elevation, shifting all
+    /// spots after it to the left.
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> i64 {
+        self.check_index(index, self.len);
+        let (mut b, k) = self.locate(index);
+        let h = self.blocks[b].height.remove(k);
+        self.len -= 1;
+        self.starts[b + 1..]
+            .iter_mut()
+            .for_each(|start| *start -= 1);
+        if self.blocks[b].height.len() < (self.block_size / 2).max(1) {
+            if self.blocks.len() > 1 {
+                b = b.min(self.blocks.len() - 2);
+                self.merge(b);
+            } else if self.len == 0 {
+                self.blocks.clear();
+                self.starts.truncate(1);
+                return h;
+            }
+        }
+        self.blocks[b].rebuild();
+        self.resize();
+        h
+    }
+
+    /// Compute the amount of rain that can be trapped in the terrain.
+    pub fn total_water(&self) -> u64 {
+        let mut before = i64::MIN;
+        let mut prefix_max_sum = 0i128;
+        for block in &self.blocks {
+            prefix_max_sum += block.prefix_max_sum(before);
+            before = before.max(block.max());
+        }
+
+        let mut after = i64::MIN;
+        let mut suffix_max_sum = 0i128;
+        for block in self.blocks.iter().rev() {
+            suffix_max_sum += block.suffix_max_sum(after);
+            after = after.max(block.max());
+        }
+
+        // before is the global maximum now
+        let sum: i128 = self.blocks.iter().map(|block| block.sum).sum();
+        (prefix_max_sum + suffix_max_sum
+            - before as i128 * self.len as i128
+            - sum) as u64
+    }
+
+    fn check_index(&self, index: usize, bound: usize) {
+        assert!(
+            index < bound,
+            "index {} out of bounds for terrain of length {}",
+            index,
+            self.len
+        );
+    }
+
+    /// Block and position within the block of the spot at `index < len`.
+    fn locate(&self, index: usize) -> (usize, usize) {
+        // the first block starting after index, blocks aren't empty
+        let b = self.starts.partition_point(|&start| start <= index) - 1;
+        (b, index - self.starts[b])
+    }
+
+    /// Split block `b` in halves, rebuilding the second half.
+    fn split(&mut self, b: usize) {
+        let half = self.blocks[b].height.len() / 2;
+        let tail = self.blocks[b].height.split_off(half);
+        self.blocks.insert(b + 1, Block::new(tail));
+        self.starts.insert(b + 1, self.starts[b] + half);
+    }
+
+    /// Merge block `b + 1` into block `b`, splitting it again if it gets
+    /// too large.
+    fn merge(&mut self, b: usize) {
+        let next = self.blocks.remove(b + 1);
+        self.starts.remove(b + 1);
+        self.blocks[b].height.extend(next.height);
+        if self.blocks[b].height.len() > 2 * self.block_size {
+            self.split(b);
+        }
+    }
+
+    /// Rebuild all blocks once the block size is off from `√n` by more
+    /// than a factor of two.
+    fn resize(&mut self) {
+        let target = self.min_block_size.max(self.len.isqrt());
+        if target > 2 * self.block_size || 2 * target < self.block_size {
+            *self = Self::with_block_size(&self.to_vec(), self.min_block_size);
+        }
+    }
+}
+
+impl From<Vec<i64>> for DynamicTerrain {
+    fn from(height: Vec<i64>) -> Self {
+        DynamicTerrain::new(&height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_rain_collected3;
+    use crate::test_util::{random, TERRAINS};
+
+    #[test]
+    fn test_total_water() {
+        for terrain in TERRAINS {
+            for block_size in [1, 2, 3, MIN_BLOCK_SIZE] {
+                let dynamic =
+                    DynamicTerrain::with_block_size(terrain, block_size);
+                assert_eq!(
+                    dynamic.total_water(),
+                    compute_rain_collected3(terrain)
+                );
+                assert_eq!(dynamic.to_vec(), terrain);
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_edits() {
+        let mut state = 0xD1B54A32D192ED03u64;
+        for block_size in [1, 2, 5, 16] {
+            let mut reference: Vec<i64> = Vec::new();
+            let mut dynamic = DynamicTerrain::with_block_size(&[], block_size);
+            for _ in 0..2000 {
+                let h = (random(&mut state) % 21) as i64 - 10;
+                let len = reference.len();
+                match random(&mut state) % 4 {
+                    0 | 1 => {
+                        let index = random(&mut state) as usize % (len + 1);
+                        reference.insert(index, h);
+                        dynamic.insert(index, h);
+                    }
+                    2 if len > 0 => {
+                        let index = random(&mut state) as usize % len;
+                        reference[index] = h;
+                        dynamic.set(index, h);
+                    }
+                    3 if len > 0 => {
+                        let index = random(&mut state) as usize % len;
+                        assert_eq!(
+                            dynamic.remove(index),
+                            reference.remove(index)
+                        );
+                    }
+                    _ => {}
+                }
+                assert_eq!(dynamic.len(), reference.len());
+                check_blocks(&dynamic);
+                assert_eq!(
+                    dynamic.total_water(),
+                    compute_rain_collected3(&reference)
+                );
+            }
+            assert_eq!(dynamic.to_vec(), reference);
+        }
+    }
+
+    /// Check the sizes of the blocks and their first indices.
+    fn check_blocks(dynamic: &DynamicTerrain) {
+        let b = dynamic.block_size;
+        let sizes: Vec<usize> = dynamic
+            .blocks
+            .iter()
+            .map(|block| block.height.len())
+            .collect();
+        if sizes.len() > 1 {
+            assert!(sizes.iter().all(|&n| b / 2 <= n && n <= 2 * b));
+        }
+        assert!(sizes.iter().all(|&n| n > 0));
+        let starts: Vec<usize> = std::iter::once(0)
+            .chain(sizes.iter().scan(0, |start, &n| {
+                *start += n;
+                Some(*start)
+            }))
+            .collect();
+        assert_eq!(dynamic.starts, starts);
+        assert_eq!(*starts.last().unwrap(), dynamic.len());
+        // √n and the block size are within a factor of two
+        let target = dynamic.min_block_size.max(dynamic.len().isqrt());
+        assert!(target <= 2 * b && b <= 2 * target);
+    }
+
+    #[test]
+    fn test_remove_most() {
+        let mut state = 0x9E6C63D0676A9A99u64;
+        let terrain: Vec<i64> = (0..20_000)
+            .map(|_| (random(&mut state) % 100) as i64)
+            .collect();
+        let mut reference = terrain.clone();
+        let mut dynamic = DynamicTerrain::with_block_size(&terrain, 4);
+        while reference.len() > 100 {
+            let index = random(&mut state) as usize % reference.len();
+            assert_eq!(dynamic.remove(index), reference.remove(index));
+            check_blocks(&dynamic);
+        }
+        // blocks of at least B / 2 spots
+        assert!(dynamic.blocks.len() <= 2 * 100 / dynamic.block_size + 1);
+        assert!(dynamic.block_size <= 2 * 100usize.isqrt());
+        assert_eq!(dynamic.total_water(), compute_rain_collected3(&reference));
+        assert_eq!(dynamic.to_vec(), reference);
+    }
+
+    #[test]
+    fn test_get() {
+        let dynamic = DynamicTerrain::with_block_size(&[3, 1, 4, 1, 5], 2);
+        assert_eq!(dynamic.get(2), Some(4));
+        assert_eq!(dynamic.get(4), Some(5));
+        assert_eq!(dynamic.get(5), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_set_out_of_bounds() {
+        DynamicTerrain::new(&[1, 2, 3]).set(3, 0);
+    }
+}
@@ -0,0 +1,278 @@
+//! # Out-of-core terrains
+//! Compute the amount of rain trapped in a terrain stored in a file as
+//! little-endian `i64` elevations, without loading the file into memory.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Default number of elevations buffered at each end of the terrain.
+const BUFFER_LEN: usize = 1 << 16;
+
+/// Compute the amount of rain that can be trapped in the terrain stored in
+/// a file.
+/// # Arguments
+/// * `path` - The path of a file of little-endian `i64` elevations.
+/// # Returns
+/// The amount of rain that can be trapped in the terrain, or an error if
+/// the file can't be read or its length isn't a multiple of 8 bytes.
+/// # Example
+/// ```no_run
+/// use trap_rust::compute_rain_collected_file;
+/// let water = compute_rain_collected_file("terrain.bin").unwrap();
+/// println!("Water capacity: {}", water);
+/// ```
+/// # Note
+/// See [`compute_rain_collected_reader`], with two buffers of 512 KiB.
+pub fn compute_rain_collected_file<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+    compute_rain_collected_reader(File::open(path)?, BUFFER_LEN)
+}
+
+/// Compute the amount of rain that can be trapped in the terrain read from
+/// a seekable reader.
+/// # Arguments
+/// * `reader` - A reader of little-endian `i64` elevations, from its current
+///   position to its end.
+/// * `buffer_len` - The number of elevations buffered at each end of the
+///   terrain, at least 1.
+/// # Returns
+/// The amount of rain that can be trapped in the terrain, or an error if
+/// reading fails or the length of the terrain isn't a multiple of 8 bytes.
+/// # Example
+/// ```
+/// use std::io::Cursor;
+/// use trap_rust::compute_rain_collected_reader;
+/// let bytes: Vec<u8> = [1i64, 4, 2, 5, 3, 6, 4, 7]
+///     .iter()
+///     .flat_map(|x| x.to_le_bytes())
+///     .collect();
+/// assert_eq!(compute_rain_collected_reader(Cursor::new(bytes), 2).unwrap(), 6);
+/// ```
+/// # Note
+/// Like compute_rain_collected, the terrain is read from both ends on a
+/// minimum first basis. Each end is read in blocks of `buffer_len`
+/// elevations, so memory use doesn't depend on the size of the terrain.
+/// # Panics
+/// Panics if `buffer_len` is 0.
+pub fn compute_rain_collected_reader<R: Read + Seek>(
+    reader: R,
+    buffer_len: usize,
+) -> io::Result<u64> {
+    assert!(buffer_len > 0, "buffer_len must be at least 1");
+    let mut ends = Ends::new(reader, buffer_len)?;
+    let water = crate::generic::compute_rain_collected_iter(&mut ends);
+    match ends.error {
+        Some(error) => Err(error),
+        None => Ok(water),
+    }
+}
+
+/// Double-ended iterator over the elevations of a reader, buffering a block
+/// at each end. An error stops the iteration and is kept in `error`.
+struct Ends<R> {
+    reader: R,
+    buffer_len: usize,
+    bytes: Vec<u8>,
+    // byte positions of the elevations not read into a buffer yet
+    unread: (u64, u64),
+    // buffered elevations and the range of them not consumed yet
+    front: Vec<i64>,
+    front_live: (usize, usize),
+    back: Vec<i64>,
+    back_live: (usize, usize),
+    error: Option<io::Error>,
+}
+
+impl<R: Read + Seek> Ends<R> {
+    fn new(mut reader: R, buffer_len: usize) -> io::Result<Self> {
+        let start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        if (end - start) % 8 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "terrain of {} bytes isn't a sequence of i64",
+                    end - start
+                ),
+            ));
+        }
+        Ok(Ends {
+            reader,
+            buffer_len,
+            bytes: Vec::new(),
+            unread: (start, end),
+            front: Vec::new(),
+            front_live: (0, 0),
+            back: Vec::new(),
+            back_live: (0, 0),
+            error: None,
+        })
+    }
+
+    /// Read the elevations at byte positions `from..to` into `buffer`.
+    fn read(&mut self, from: u64, to: u64, buffer: &mut Vec<i64>) {
+        buffer.clear();
+        self.bytes.resize((to - from) as usize, 0);
+        let read = self
+            .reader
+            .seek(SeekFrom::Start(from))
+            .and_then(|_| self.reader.read_exact(&mut self.bytes));
+        match read {
+            Ok(()) => buffer.extend(
+                self.bytes
+                    .chunks_exact(8)
+                    .map(|b| i64::from_le_bytes(b.try_into().unwrap())),
+            ),
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// Read the next block from the front into the front buffer, or, if
+    /// everything has been read, move the rest of the back buffer there.
+    fn refill_front(&mut self) {
+        let (from, end) = self.unread;
+        if from < end {
+            let to = end.min(from + 8 * self.buffer_len as u64);
+            let mut front = std::mem::take(&mut self.front);
+            self.read(from, to, &mut front);
+            self.front = front;
+            self.unread.0 = to;
+            self.front_live = (0, self.front.len());
+        } else {
+            std::mem::swap(&mut self.front, &mut self.back);
+            self.front_live = self.back_live;
+            self.back_live = (0, 0);
+        }
+    }
+
+    /// Read the next block from the back into the back buffer, or, if
+    /// everything has been read, move the rest of the front buffer there.
+    fn refill_back(&mut self) {
+        let (start, to) = self.unread;
+        if start < to {
+            let from = start.max(to.saturating_sub(8 * self.buffer_len as u64));
+            let mut back = std::mem::take(&mut self.back);
+            self.read(from, to, &mut back);
+            self.back = back;
+            self.unread.1 = from;
+            self.back_live = (0, self.back.len());
+        } else {
+            std::mem::swap(&mut self.front, &mut self.back);
+            self.back_live = self.front_live;
+            self.front_live = (0, 0);
+        }
+    }
+}
+
+impl<R: Read + Seek> Iterator for Ends<R> {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        if self.front_live.0 == self.front_live.1 {
+            self.refill_front();
+        }
+        let (first, end) = &mut self.front_live;
+        if self.error.is_some() || first == end {
+            return None;
+        }
+        *first += 1;
+        Some(self.front[*first - 1])
+    }
+}
+
+impl<R: Read + Seek> DoubleEndedIterator for Ends<R> {
+    fn next_back(&mut self) -> Option<i64> {
+        if self.back_live.0 == self.back_live.1 {
+            self.refill_back();
+        }
+        let (first, end) = &mut self.back_live;
+        if self.error.is_some() || first == end {
+            return None;
+        }
+        *end -= 1;
+        Some(self.back[*end])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random, TERRAINS};
+    use crate::{compute_rain_collected, RainAccumulator};
+    use std::io::{BufWriter, Cursor, Write};
+
+    /// A temporary file, removed when dropped, also if a test panics.
+    struct TempFile(std::path::PathBuf);
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn to_bytes(terrain: &[i64]) -> Vec<u8> {
+        terrain.iter().flat_map(|x| x.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_compute_rain_collected_reader() {
+        for terrain in TERRAINS {
+            for buffer_len in [1, 2, 3, 5, 100] {
+                let reader = Cursor::new(to_bytes(terrain));
+                assert_eq!(
+                    compute_rain_collected_reader(reader, buffer_len).unwrap(),
+                    compute_rain_collected(terrain),
+                    "buffer of {} on {:?}",
+                    buffer_len,
+                    terrain
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_reader_starts_at_current_position() {
+        let mut reader = Cursor::new(to_bytes(&[9, 0, 2, 0, 1]));
+        reader.set_position(8);
+        assert_eq!(compute_rain_collected_reader(reader, 2).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_truncated_terrain() {
+        let mut bytes = to_bytes(&[3, 0, 3]);
+        bytes.pop();
+        let error =
+            compute_rain_collected_reader(Cursor::new(bytes), 4).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_compute_rain_collected_file() {
+        // 320 MB of terrain, far more than the buffers hold
+        const N: usize = 40_000_000;
+        let path = TempFile(
+            std::env::temp_dir()
+                .join(format!("trap_rust_terrain_{}.bin", std::process::id())),
+        );
+        let mut file = BufWriter::new(File::create(&path.0).unwrap());
+        let mut rain = RainAccumulator::new();
+        let mut state = 0xBB67AE8584CAA73Bu64;
+        for _ in 0..N {
+            let h = (random(&mut state) % 1_000_000) as i64;
+            rain.push(h);
+            file.write_all(&h.to_le_bytes()).unwrap();
+        }
+        // flush the buffer, failing on write errors
+        file.into_inner().unwrap();
+
+        let water = compute_rain_collected_file(&path.0).unwrap();
+        assert_eq!(water, rain.finish());
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let error =
+            compute_rain_collected_file("does/not/exist.bin").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,311 @@
+//! # Floating point terrains
+//! Variants of compute_rain_collected and trap for real valued elevations.
+//! The amount of rain trapped is summed up using Neumaier's variant of
+//! Kahan summation, and `NaN` elevations are handled according to a
+//! [`NanPolicy`].
+//!
+//! # Error bound
+//! The water collected on each spot is computed as a single rounded `f64`
+//! subtraction, with a relative error of at most `ε = 2^-53`. As all these
+//! depths are non-negative, compensated summation adds them up with a
+//! relative error of at most `2ε + O(nε²)`, so the result differs from the
+//! exact amount of rain trapped in the terrain by at most
+//! `(3ε + O(nε²)) * exact`, independent of the order of magnitude of the
+//! elevations. Plain summation only guarantees `(n - 1)ε * exact`.
+
+use std::fmt;
+
+use crate::generic::{fold_depths, fold_trap};
+use crate::Elevation;
+
+/// How to treat `NaN` elevations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum NanPolicy {
+    /// Skip `NaN` spots: they hold no water and do not act as walls, as if
+    /// removed from the terrain.
+    #[default]
+    Skip,
+    /// Fail with a [`NanError`] if the terrain contains a `NaN` spot.
+    Reject,
+}
+
+/// Error returned for a terrain containing `NaN` under [`NanPolicy::Reject`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NanError {
+    /// Index of the first `NaN` spot of the terrain.
+    pub index: usize,
+}
+
+impl fmt::Display for NanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "elevation at index {} is NaN", self.index)
+    }
+}
+
+impl std::error::Error for NanError {}
+
+/// Floating point elevation types.
+trait Float: Elevation<Accumulator = f64> {
+    fn is_nan(self) -> bool;
+}
+
+impl Float for f32 {
+    fn is_nan(self) -> bool {
+        f32::is_nan(self)
+    }
+}
+
+impl Float for f64 {
+    fn is_nan(self) -> bool {
+        f64::is_nan(self)
+    }
+}
+
+/// Neumaier's improved Kahan summation.
+#[derive(Debug, Clone, Copy, Default)]
+struct CompensatedSum {
+    sum: f64,
+    compensation: f64, // the low order bits lost in sum
+}
+
+impl CompensatedSum {
+    fn add(&mut self, x: f64) {
+        let t = self.sum + x;
+        if self.sum.abs() >= x.abs() {
+            self.compensation += (self.sum - t) + x;
+        } else {
+            self.compensation += (x - t) + self.sum;
+        }
+        self.sum = t;
+    }
+
+    fn value(&self) -> f64 {
+        if self.sum.is_finite() {
+            self.sum + self.compensation
+        } else {
+            // the compensation is NaN once the sum overflowed
+            self.sum
+        }
+    }
+}
+
+/// Add the depth of the water on a spot to the sum.
+fn add(mut sum: CompensatedSum, depth: f64) -> CompensatedSum {
+    sum.add(depth);
+    sum
+}
+
+fn check_nan<T: Float>(
+    height: &[T],
+    policy: NanPolicy,
+) -> Result<(), NanError> {
+    match policy {
+        NanPolicy::Skip => Ok(()),
+        NanPolicy::Reject => match height.iter().position(|x| x.is_nan()) {
+            Some(index) => Err(NanError { index }),
+            None => Ok(()),
+        },
+    }
+}
+
+fn compute_rain_collected_float<T: Float>(
+    height: &[T],
+    policy: NanPolicy,
+) -> Result<f64, NanError> {
+    check_nan(height, policy)?;
+    Ok(
+        fold_depths(height.iter().copied(), CompensatedSum::default(), add)
+            .value(),
+    )
+}
+
+fn trap_float<T: Float>(
+    height: &[T],
+    policy: NanPolicy,
+) -> Result<f64, NanError> {
+    check_nan(height, policy)?;
+    Ok(fold_trap(height, CompensatedSum::default(), add).value())
+}
+
+/// Compute the amount of rain that can be trapped in a terrain of `f64`
+/// elevations.
+/// # Arguments
+/// * `height` - A slice of floats representing the elevation of the terrain.
+/// * `policy` - How to treat `NaN` elevations.
+/// # Returns
+/// The amount of rain that can be trapped in the terrain, or a [`NanError`]
+/// if the terrain contains `NaN` and the policy is [`NanPolicy::Reject`].
+/// # Example
+/// ```
+/// use trap_rust::{compute_rain_collected_f64, NanError, NanPolicy};
+/// let terrain = [1.0, 0.25, 0.5, 2.0];
+/// let water = compute_rain_collected_f64(&terrain, NanPolicy::Reject);
+/// assert_eq!(water, Ok(1.25));
+/// let terrain = [2.0, f64::NAN, 1.5, 2.0];
+/// let water = compute_rain_collected_f64(&terrain, NanPolicy::Skip);
+/// assert_eq!(water, Ok(0.5));
+/// let water = compute_rain_collected_f64(&terrain, NanPolicy::Reject);
+/// assert_eq!(water, Err(NanError { index: 1 }));
+/// ```
+pub fn compute_rain_collected_f64(
+    height: &[f64],
+    policy: NanPolicy,
+) -> Result<f64, NanError> {
+    compute_rain_collected_float(height, policy)
+}
+
+/// Variant of compute_rain_collected_f64 for `f32` elevations, summing up
+/// the amount of rain trapped in `f64`.
+pub fn compute_rain_collected_f32(
+    height: &[f32],
+    policy: NanPolicy,
+) -> Result<f64, NanError> {
+    compute_rain_collected_float(height, policy)
+}
+
+/// Variant of trap for `f64` elevations, see compute_rain_collected_f64.
+pub fn trap_f64(height: &[f64], policy: NanPolicy) -> Result<f64, NanError> {
+    trap_float(height, policy)
+}
+
+/// Variant of trap for `f32` elevations, see compute_rain_collected_f64.
+pub fn trap_f32(height: &[f32], policy: NanPolicy) -> Result<f64, NanError> {
+    trap_float(height, policy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generic;
+    use crate::test_util::random;
+
+    const SCALE: f64 = (1u64 << 30) as f64;
+
+    /// Exact amount of rain trapped as a rational number with denominator
+    /// 2^30, for elevations that are multiples of 2^-30 below 2^60.
+    fn exact(terrain: &[f64]) -> u128 {
+        let scaled: Vec<i128> =
+            terrain.iter().map(|&x| (x * SCALE) as i128).collect();
+        generic::compute_rain_collected(&scaled)
+    }
+
+    fn check(terrain: &[f64]) {
+        let exact = exact(terrain);
+        // A single rounding of the exact rational.
+        let expected = exact as f64 / SCALE;
+        let bound = 4.0 * f64::EPSILON * expected;
+        for water in [
+            compute_rain_collected_f64(terrain, NanPolicy::Reject).unwrap(),
+            trap_f64(terrain, NanPolicy::Reject).unwrap(),
+        ] {
+            assert!(
+                (water - expected).abs() <= bound,
+                "{} differs from {} for {:?}",
+                water,
+                expected,
+                terrain
+            );
+        }
+    }
+
+    #[test]
+    fn test_rational_oracle() {
+        let terrains: [&[f64]; 6] = [
+            &[],
+            &[1.5],
+            &[1.0, 4.0, 2.0, 5.0, 3.0, 6.0, 4.0, 7.0],
+            &[0.0, -6.25, 0.0, -2.5, 8.0, -9.125, 0.0, 8.0, 9.0, -5.0],
+            &[0.1, 0.7, 0.2, 0.3, 0.6],
+            &[1e12, 1e-6, 3e-9, 2e11, 5e-7, 1e12],
+        ];
+        for terrain in terrains {
+            // round to multiples of 2^-30 so the oracle is exact
+            let terrain: Vec<f64> = terrain
+                .iter()
+                .map(|x| (x * SCALE).round() / SCALE)
+                .collect();
+            check(&terrain);
+        }
+
+        let mut state = 0x2545F4914F6CDD1D;
+        for n in 1..64 {
+            let terrain: Vec<f64> = (0..n)
+                .map(|_| {
+                    let magnitude = (random(&mut state) % 50) as i32;
+                    let mantissa = (random(&mut state) >> 11) as f64;
+                    let sign = (random(&mut state) & 2) as f64 - 1.0;
+                    let x = sign * mantissa * 2f64.powi(magnitude - 25 - 53);
+                    (x * SCALE).round() / SCALE
+                })
+                .collect();
+            check(&terrain);
+        }
+    }
+
+    #[test]
+    fn test_compensation() {
+        // 2^53 + 1.0 == 2^53 in plain f64 summation
+        let wall = (1u64 << 53) as f64;
+        let mut terrain = vec![wall, 0.0];
+        terrain.extend(std::iter::repeat_n(wall - 1.0, 1000));
+        terrain.push(wall);
+        let expected = wall + 1000.0;
+        assert_eq!(generic::compute_rain_collected(&terrain), wall);
+        assert_eq!(
+            compute_rain_collected_f64(&terrain, NanPolicy::Skip),
+            Ok(expected)
+        );
+        assert_eq!(trap_f64(&terrain, NanPolicy::Skip), Ok(expected));
+
+        let wall = (1u32 << 24) as f32;
+        let mut terrain = vec![wall, 0.0];
+        terrain.extend(std::iter::repeat_n(wall - 1.0, 1000));
+        terrain.push(wall);
+        let expected = wall as f64 + 1000.0;
+        assert_eq!(
+            compute_rain_collected_f32(&terrain, NanPolicy::Skip),
+            Ok(expected)
+        );
+        assert_eq!(trap_f32(&terrain, NanPolicy::Skip), Ok(expected));
+    }
+
+    #[test]
+    fn test_nan_policy() {
+        let nan = f64::NAN;
+        let terrain = [3.0, 1.0, nan, 0.0, nan, 2.0, nan];
+        assert_eq!(
+            compute_rain_collected_f64(&terrain, NanPolicy::Skip),
+            Ok(3.0)
+        );
+        assert_eq!(trap_f64(&terrain, NanPolicy::Skip), Ok(3.0));
+        let error = Err(NanError { index: 2 });
+        assert_eq!(
+            compute_rain_collected_f64(&terrain, NanPolicy::Reject),
+            error
+        );
+        assert_eq!(trap_f64(&terrain, NanPolicy::Reject), error);
+
+        let terrain = [f32::NAN, 2.0, 1.0, 2.0];
+        assert_eq!(
+            compute_rain_collected_f32(&terrain, NanPolicy::Skip),
+            Ok(1.0)
+        );
+        assert_eq!(trap_f32(&terrain, NanPolicy::Skip), Ok(1.0));
+        let error = Err(NanError { index: 0 });
+        assert_eq!(
+            compute_rain_collected_f32(&terrain, NanPolicy::Reject),
+            error
+        );
+        assert_eq!(trap_f32(&terrain, NanPolicy::Reject), error);
+    }
+
+    #[test]
+    fn test_infinite() {
+        let terrain = [f64::INFINITY, 0.0, f64::INFINITY];
+        assert_eq!(
+            compute_rain_collected_f64(&terrain, NanPolicy::Reject),
+            Ok(f64::INFINITY)
+        );
+        assert_eq!(trap_f64(&terrain, NanPolicy::Reject), Ok(f64::INFINITY));
+    }
+}
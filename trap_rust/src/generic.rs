@@ -0,0 +1,436 @@
+//! # Generic elevation types
+//! The algorithms of this crate for terrains of any integer or floating
+//! point elevation type, so samples do not need to be converted to `i64`
+//! first. The `i64` functions at the crate root are specializations of
+//! these.
+//!
+//! # NaN policy
+//! A `NaN` elevation of a floating point terrain is skipped: the spot holds
+//! no water and does not act as a wall, so the terrain traps the same amount
+//! of water as the terrain with that spot removed.
+
+use std::ops::Add;
+
+/// Elevation of a spot of the terrain.
+pub trait Elevation: Copy {
+    /// Type the amount of rain trapped is summed up in.
+    type Accumulator: Copy + Default + Add<Output = Self::Accumulator>;
+
+    /// Elevation not higher than any spot of a terrain, the initial step of
+    /// the stair.
+    const LOWEST: Self;
+
+    /// Whether `self` is lower than or equal to `other`. `NaN` is lower than
+    /// everything else.
+    fn lower_or_equal(self, other: Self) -> bool;
+
+    /// The higher of `self` and `other`, ignoring `NaN`.
+    fn higher(self, other: Self) -> Self;
+
+    /// The depth of the water on a spot of elevation `self` if the water
+    /// surface is at `level`, which is not lower than `self` unless `self`
+    /// is `NaN`.
+    fn depth(self, level: Self) -> Self::Accumulator;
+}
+
+macro_rules! impl_elevation {
+    ($($t:ty => $acc:ty, |$x:ident, $level:ident| $depth:expr;)*) => {
+        $(
+            impl Elevation for $t {
+                type Accumulator = $acc;
+
+                const LOWEST: Self = <$t>::MIN;
+
+                #[inline]
+                fn lower_or_equal(self, other: Self) -> bool {
+                    self <= other
+                }
+
+                #[inline]
+                fn higher(self, other: Self) -> Self {
+                    Ord::max(self, other)
+                }
+
+                #[inline]
+                fn depth(self, level: Self) -> $acc {
+                    let ($x, $level) = (self, level);
+                    $depth
+                }
+            }
+        )*
+    };
+}
+
+impl_elevation! {
+    i8 => u64, |x, level| (level as i64 - x as i64) as u64;
+    i16 => u64, |x, level| (level as i64 - x as i64) as u64;
+    i32 => u64, |x, level| (level as i64 - x as i64) as u64;
+    // The difference always fits into a u64, even if it overflows i64.
+    i64 => u64, |x, level| level.wrapping_sub(x) as u64;
+    isize => u64, |x, level| (level as i64).wrapping_sub(x as i64) as u64;
+    i128 => u128, |x, level| level.wrapping_sub(x) as u128;
+    u8 => u64, |x, level| (level - x) as u64;
+    u16 => u64, |x, level| (level - x) as u64;
+    u32 => u64, |x, level| (level - x) as u64;
+    u64 => u64, |x, level| level - x;
+    usize => u64, |x, level| (level - x) as u64;
+    u128 => u128, |x, level| level - x;
+}
+
+macro_rules! impl_elevation_float {
+    ($($t:ty),*) => {
+        $(
+            impl Elevation for $t {
+                type Accumulator = f64;
+
+                const LOWEST: Self = <$t>::NEG_INFINITY;
+
+                #[inline]
+                fn lower_or_equal(self, other: Self) -> bool {
+                    self.is_nan() || self <= other
+                }
+
+                #[inline]
+                fn higher(self, other: Self) -> Self {
+                    <$t>::max(self, other)
+                }
+
+                #[inline]
+                fn depth(self, level: Self) -> f64 {
+                    // false for NaN, and for infinite spots level with
+                    // the water
+                    if level > self {
+                        level as f64 - self as f64
+                    } else {
+                        0.0
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_elevation_float!(f32, f64);
+
+/// Compute the amount of rain that can be trapped in the terrain.
+/// # Arguments
+/// * `height` - A slice representing the elevation of the terrain.
+/// # Returns
+/// The amount of rain that can be trapped in the terrain.
+/// # Example
+/// ```
+/// use trap_rust::generic::compute_rain_collected;
+/// let terrain: [u16; 8] = [1, 4, 2, 5, 3, 6, 4, 7];
+/// assert_eq!(compute_rain_collected(&terrain), 6);
+/// let terrain = [1.0, 4.0, 2.5, f64::NAN, 5.0];
+/// assert_eq!(compute_rain_collected(&terrain), 1.5);
+/// ```
+/// # Note
+/// See [`crate::compute_rain_collected`] for how it works.
+pub fn compute_rain_collected<T: Elevation>(height: &[T]) -> T::Accumulator {
+    compute_rain_collected_iter(height.iter().copied())
+}
+
+/// Variant of compute_rain_collected consuming any double-ended iterator
+/// of elevations.
+/// # Arguments
+/// * `height` - The elevations of the terrain, which are read from both
+///   ends.
+/// # Returns
+/// The amount of rain that can be trapped in the terrain.
+/// # Example
+/// ```
+/// use trap_rust::generic::compute_rain_collected_iter;
+/// let terrain: [u8; 4] = [3, 1, 0, 2];
+/// assert_eq!(compute_rain_collected_iter(terrain.iter().map(|&x| x as i32)), 3);
+/// ```
+pub fn compute_rain_collected_iter<T, I>(height: I) -> T::Accumulator
+where
+    T: Elevation,
+    I: IntoIterator<Item = T>,
+    I::IntoIter: DoubleEndedIterator,
+{
+    fold_depths(height, T::Accumulator::default(), |water, depth| {
+        water + depth
+    })
+}
+
+/// The fold of compute_rain_collected_iter, adding up the depth of the
+/// water on each spot with `add`, starting from `init`.
+pub(crate) fn fold_depths<T, I, A>(
+    height: I,
+    init: A,
+    mut add: impl FnMut(A, T::Accumulator) -> A,
+) -> A
+where
+    T: Elevation,
+    I: IntoIterator<Item = T>,
+    I::IntoIter: DoubleEndedIterator,
+{
+    let mut height = height.into_iter();
+
+    std::iter::repeat(())
+        // We reorder the sequence of elevations by taking values
+        // from both ends of the terrain on a minimum first basis,
+        // advancing the iterator that points to the smaller value.
+        // This way we are guaranteed to have the maximum elevation
+        // as the last spot.
+        .scan((height.next(), height.next_back()), |state, _| {
+            if let (Some(left), Some(right)) = *state {
+                if left.lower_or_equal(right) {
+                    *state = (height.next(), Some(right));
+                    Some(left)
+                } else {
+                    *state = (Some(left), height.next_back());
+                    Some(right)
+                }
+            } else {
+                None
+            }
+        })
+        // The newly ordered sequence traps the same amount of
+        // water as the former one (yes, you got it, proof is left
+        // to the reader). We can now apply the fold operation
+        // described above for the "maximum is last" case.
+        .fold(
+            (
+                T::LOWEST, // keeps track of the stepsize of the stair
+                init,      // keeps track of the water collected
+            ),
+            |acc, x| {
+                let stepsize = x.higher(acc.0);
+                (stepsize, add(acc.1, x.depth(stepsize)))
+            },
+        )
+        .1 // we are only interested in the water collected
+}
+
+/// previous solution
+pub fn compute_rain_collected3<T: Elevation>(terrain: &[T]) -> T::Accumulator {
+    let n = terrain.len();
+    if n < 3 {
+        return T::Accumulator::default();
+    }
+
+    // Water + terrain create a stair up left to the maximum elevation and a
+    // stair down right to the maximum elevation. The elevation of each step of
+    // the stair is the maximum elevation seen so far walking towards the
+    // maximum elevation from either side. Water collected is determined by the
+    // difference between the elevation of the current step and the actual
+    // elevation.
+
+    // Like max_by_key, this picks the last of several maximum elevations.
+    let index_maximum = terrain.iter().enumerate().fold(0, |m, (i, &v)| {
+        if terrain[m].lower_or_equal(v) {
+            i
+        } else {
+            m
+        }
+    });
+
+    let (terrain_left_of_max_elevation, terrain_right_of_max_elevation) =
+        terrain.split_at(index_maximum);
+
+    // We calculate the water collected on the left side of the maximum elevation.
+    let water_capacity_left = terrain_left_of_max_elevation
+        .iter()
+        .fold(
+            (
+                T::LOWEST,                 // .0: tracks maximum elevation
+                T::Accumulator::default(), // .1: tracks water collected
+            ),
+            |acc, &x| {
+                let stepsize = x.higher(acc.0); // update the maximum elevation seen so far
+                (stepsize, acc.1 + x.depth(stepsize))
+            },
+        )
+        .1; // we are only interested in the water collected
+
+    // This time we calculate the water collected on the right side of the
+    // maximum elevation. We do this by iterating the terrain right to the max
+    // elevation applying the same fold operation in reverse order.
+    let water_capacity_right = terrain_right_of_max_elevation
+        .iter()
+        .rfold((T::LOWEST, T::Accumulator::default()), |acc, &x| {
+            let stepsize = x.higher(acc.0);
+            (stepsize, acc.1 + x.depth(stepsize))
+        })
+        .1;
+
+    water_capacity_left + water_capacity_right
+}
+
+/// fastest solution from leetcode
+pub fn trap<T: Elevation>(height: &[T]) -> T::Accumulator {
+    fold_trap(height, T::Accumulator::default(), |water, depth| {
+        water + depth
+    })
+}
+
+/// fastest solution from leetcode, consuming input
+pub fn trap_v<T: Elevation>(height: Vec<T>) -> T::Accumulator {
+    trap(&height)
+}
+
+/// fastest solution from leetcode, avoiding bounds checks
+#[allow(unsafe_code)]
+pub fn trap_unsafe<T: Elevation>(height: &[T]) -> T::Accumulator {
+    let (mut left, mut right) = (0, height.len().saturating_sub(1));
+    let mut pool_height = T::LOWEST;
+    let mut trapped = T::Accumulator::default();
+
+    while left < right {
+        // SAFETY: left < right < height.len()
+        let (leftv, rightv) = unsafe {
+            (*height.get_unchecked(left), *height.get_unchecked(right))
+        };
+        if leftv.lower_or_equal(rightv) {
+            pool_height = pool_height.higher(leftv);
+            trapped = trapped + leftv.depth(pool_height);
+            left += 1;
+        } else {
+            pool_height = pool_height.higher(rightv);
+            trapped = trapped + rightv.depth(pool_height);
+            right -= 1;
+        }
+    }
+
+    trapped
+}
+
+/// The two-pointer loop of trap, adding up the depth of the water on each
+/// spot with `add`, starting from `init`.
+pub(crate) fn fold_trap<T: Elevation, A>(
+    height: &[T],
+    init: A,
+    mut add: impl FnMut(A, T::Accumulator) -> A,
+) -> A {
+    let (mut left, mut right) = (0, height.len().saturating_sub(1));
+    let mut pool_height = T::LOWEST;
+    let mut trapped = init;
+
+    while left < right {
+        // The lower of both ends raises the pool if it is higher.
+        if height[left].lower_or_equal(height[right]) {
+            pool_height = pool_height.higher(height[left]);
+            trapped = add(trapped, height[left].depth(pool_height));
+            left += 1;
+        } else {
+            pool_height = pool_height.higher(height[right]);
+            trapped = add(trapped, height[right].depth(pool_height));
+            right -= 1;
+        }
+    }
+
+    trapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TERRAINS: [(&[i8], u64); 11] = [
+        (&[0, 0, 0, 0, 0], 0),
+        (&[1, 2, 3, 4, 5], 0),
+        (&[5, 4, 3, 2, 1], 0),
+        (&[1, 2, 3, 2, 1], 0),
+        (&[1, 2, 3, 2, 4, 1], 1),
+        (&[1, 4, 2, 5, 3, 6, 4, 7], 6),
+        (&[2, 1, 2], 1),
+        (&[5, 4, 2, 6, 6, 6, 4, 5], 5),
+        (&[0, 1, -1, 2, 1, 0, 1, 3, 2, 1, 2, 1], 7),
+        (&[4, 2, 0, 3, 2, 5], 9),
+        (&[0, -6, 0, -2, 8, -9, 0, 8, 9, -5], 33),
+    ];
+
+    fn check<T: Elevation>(terrain: &[T], water: T::Accumulator)
+    where
+        T::Accumulator: PartialEq + std::fmt::Debug,
+    {
+        assert_eq!(compute_rain_collected(terrain), water);
+        assert_eq!(compute_rain_collected3(terrain), water);
+        assert_eq!(trap(terrain), water);
+        assert_eq!(trap_v(terrain.to_vec()), water);
+        assert_eq!(trap_unsafe(terrain), water);
+    }
+
+    #[test]
+    fn test_signed() {
+        for (terrain, water) in TERRAINS {
+            check(terrain, water);
+            check(
+                &terrain.iter().map(|&x| x as i16).collect::<Vec<_>>(),
+                water,
+            );
+            check(
+                &terrain.iter().map(|&x| x as i32).collect::<Vec<_>>(),
+                water,
+            );
+            check(
+                &terrain.iter().map(|&x| x as i64).collect::<Vec<_>>(),
+                water,
+            );
+            check(
+                &terrain.iter().map(|&x| x as isize).collect::<Vec<_>>(),
+                water,
+            );
+            check(
+                &terrain.iter().map(|&x| x as i128).collect::<Vec<_>>(),
+                water as u128,
+            );
+        }
+        check(&[i8::MAX, i8::MIN, i8::MAX], 255);
+        check(&[i64::MAX, i64::MIN, i64::MAX], u64::MAX);
+    }
+
+    #[test]
+    fn test_unsigned() {
+        for (terrain, water) in TERRAINS {
+            // shift the terrain above zero, which keeps the water trapped
+            let shifted = terrain.iter().map(|&x| (x as i16 + 10) as u8);
+            check(&shifted.clone().collect::<Vec<_>>(), water);
+            let shifted = shifted.map(u16::from);
+            check(&shifted.clone().collect::<Vec<_>>(), water);
+            let shifted = shifted.map(u32::from);
+            check(&shifted.clone().collect::<Vec<_>>(), water);
+            let shifted = shifted.map(u64::from);
+            check(&shifted.clone().collect::<Vec<_>>(), water);
+            check(
+                &shifted.clone().map(|x| x as usize).collect::<Vec<_>>(),
+                water,
+            );
+            check(&shifted.map(u128::from).collect::<Vec<_>>(), water as u128);
+        }
+        check(&[u16::MAX, 0, u16::MAX], u16::MAX as u64);
+    }
+
+    #[test]
+    fn test_float() {
+        for (terrain, water) in TERRAINS {
+            check(
+                &terrain.iter().map(|&x| x as f32).collect::<Vec<_>>(),
+                water as f64,
+            );
+            check(
+                &terrain.iter().map(|&x| x as f64).collect::<Vec<_>>(),
+                water as f64,
+            );
+        }
+        check(&[2.5f32, 0.25, 3.0], 2.25);
+        check(&[f64::INFINITY, 0.0, f64::INFINITY], f64::INFINITY);
+        check(&[f64::NEG_INFINITY, f64::NEG_INFINITY], 0.0);
+    }
+
+    #[test]
+    fn test_float_nan() {
+        let nan = f64::NAN;
+        // NaN spots are skipped, as if removed from the terrain
+        check(&[nan], 0.0);
+        check(&[nan, nan, nan], 0.0);
+        check(&[nan, 2.0, 1.0, 2.0, nan], 1.0);
+        check(&[2.0, nan, 2.0], 0.0);
+        check(&[2.0, nan, 0.0, 2.0], 2.0);
+        check(&[3.0, 1.0, nan, 0.0, nan, 2.0, nan], 3.0);
+        check(&[nan, 9.0, 1.0, nan, 2.0, 0.0, 9.0], 24.0);
+    }
+}
@@ -0,0 +1,330 @@
+//! # Rain Trapped in Grid Terrain
+//! Given a terrain represented by a grid of integers in row-major order,
+//! each representing the elevation of a spot, calculate the amount of
+//! rain that can be trapped in the terrain. Water flows between spots
+//! sharing an edge and drains off the border of the grid.
+//!
+//! A dimension of extent one is treated as a cross-section with closed
+//! sides, so a grid made of a single row (or a single column) traps the
+//! same amount of water as the one dimensional terrain, see
+//! [`compute_rain_collected`](crate::compute_rain_collected).
+//!
+//! Drains are spots where the water is lost, as off the border of the
+//! grid, see [`trap_2d_with_drains`].
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Compute the amount of rain that can be trapped in the grid terrain.
+/// # Arguments
+/// * `heights` - A slice of integers representing the elevation of the
+///   terrain in row-major order.
+/// * `width` - The number of spots in each row.
+/// * `height` - The number of rows.
+/// # Returns
+/// The amount of rain that can be trapped in the terrain.
+/// # Panics
+/// Panics if `heights.len()` is not `width * height`.
+/// # Example
+/// ```
+/// use trap_rust::grid::trap_2d;
+/// let terrain = [
+///     1, 4, 3, 1, 3, 2, //
+///     3, 2, 1, 3, 2, 4, //
+///     2, 3, 3, 2, 3, 1,
+/// ];
+/// assert_eq!(trap_2d(&terrain, 6, 3), 4);
+/// ```
+pub fn trap_2d(heights: &[i64], width: usize, height: usize) -> u64 {
+    water_levels_2d(heights, width, height)
+        .iter()
+        .zip(heights)
+        // The difference always fits into a u64, even if it overflows i64.
+        .map(|(&level, &x)| level.wrapping_sub(x) as u64)
+        .sum()
+}
+
+/// Compute the elevation of the water surface for each spot of the grid.
+/// # Arguments
+/// * `heights` - A slice of integers representing the elevation of the
+///   terrain in row-major order.
+/// * `width` - The number of spots in each row.
+/// * `height` - The number of rows.
+/// # Returns
+/// The elevation of the water surface for each spot in row-major order,
+/// which equals the elevation of the spot if no water is trapped there.
+/// # Panics
+/// Panics if `heights.len()` is not `width * height`.
+/// # Note
+/// The water surface is found by flooding the terrain from its border
+/// inwards. The border spots are kept in a priority queue, and the lowest
+/// of them is always expanded first: its unvisited neighbours can hold
+/// water up to the level of that spot, as every other path to the border
+/// has to cross a spot at least as high. A neighbour becomes part of the
+/// border with the maximum of its own elevation and that level.
+pub fn water_levels_2d(
+    heights: &[i64],
+    width: usize,
+    height: usize,
+) -> Vec<i64> {
+    flood(heights, width, height, |_| false)
+}
+
+/// Compute the amount of rain that can be trapped in the grid terrain with
+/// drains.
+/// # Arguments
+/// * `heights` - A slice of the elevations of the terrain in row-major
+///   order, `None` for a drain.
+/// * `width` - The number of spots in each row.
+/// * `height` - The number of rows.
+/// # Returns
+/// The amount of rain that can be trapped in the terrain.
+/// # Panics
+/// Panics if `heights.len()` is not `width * height`.
+/// # Example
+/// ```
+/// use trap_rust::grid::trap_2d_with_drains;
+/// let terrain = [
+///     Some(3), Some(3), Some(3), Some(3), //
+///     Some(3), Some(1), Some(2), Some(3), //
+///     Some(3), Some(2), None, Some(3), //
+///     Some(3), Some(3), Some(3), Some(3),
+/// ];
+/// assert_eq!(trap_2d_with_drains(&terrain, 4, 4), 1);
+/// ```
+/// # Note
+/// A drain holds no water. The terrain is flooded as in
+/// [`water_levels_2d`], with the drains added to the border at the lowest
+/// level, so the water next to them drains down to the lowest barrier
+/// between it and a drain.
+pub fn trap_2d_with_drains(
+    heights: &[Option<i64>],
+    width: usize,
+    height: usize,
+) -> u64 {
+    let floor: Vec<i64> =
+        heights.iter().map(|h| h.unwrap_or(i64::MIN)).collect();
+    flood(&floor, width, height, |i| heights[i].is_none())
+        .iter()
+        .zip(heights)
+        // The difference always fits into a u64, even if it overflows i64.
+        .filter_map(|(&level, &x)| x.map(|x| level.wrapping_sub(x) as u64))
+        .sum()
+}
+
+/// Flood the grid from its border and from the spots where `drain` is true.
+fn flood(
+    heights: &[i64],
+    width: usize,
+    height: usize,
+    drain: impl Fn(usize) -> bool,
+) -> Vec<i64> {
+    assert_eq!(
+        heights.len(),
+        width * height,
+        "grid of {} spots does not match {}x{}",
+        heights.len(),
+        width,
+        height
+    );
+
+    let mut levels = heights.to_vec();
+    let mut visited = vec![false; heights.len()];
+    let mut border = BinaryHeap::new();
+
+    let mut seed = |i: usize| {
+        if !visited[i] {
+            visited[i] = true;
+            border.push(Reverse((heights[i], i)));
+        }
+    };
+    // Edges of a dimension of extent one are closed, water drains off the
+    // other ones.
+    if height > 1 {
+        (0..width).for_each(&mut seed);
+        ((height - 1) * width..height * width).for_each(&mut seed);
+    }
+    if width > 1 {
+        (0..height).map(|r| r * width).for_each(&mut seed);
+        (0..height)
+            .map(|r| r * width + width - 1)
+            .for_each(&mut seed);
+    }
+    // Drains are lower than any spot, so they are expanded first.
+    (0..heights.len()).filter(|&i| drain(i)).for_each(&mut seed);
+
+    while let Some(Reverse((level, i))) = border.pop() {
+        let (row, column) = (i / width, i % width);
+        let neighbours = [
+            (row > 0).then(|| i - width),
+            (row + 1 < height).then(|| i + width),
+            (column > 0).then(|| i - 1),
+            (column + 1 < width).then(|| i + 1),
+        ];
+        for j in neighbours.into_iter().flatten() {
+            if !visited[j] {
+                visited[j] = true;
+                levels[j] = heights[j].max(level);
+                border.push(Reverse((levels[j], j)));
+            }
+        }
+    }
+
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TERRAINS;
+    use crate::{compute_rain_collected, trap_with_drains};
+
+    #[test]
+    fn test_trap_2d() {
+        let terrain1 = [
+            1, 4, 3, 1, 3, 2, //
+            3, 2, 1, 3, 2, 4, //
+            2, 3, 3, 2, 3, 1,
+        ];
+        assert_eq!(trap_2d(&terrain1, 6, 3), 4);
+
+        let terrain2 = [
+            3, 3, 3, 3, 3, //
+            3, 2, 2, 2, 3, //
+            3, 2, 1, 2, 3, //
+            3, 2, 2, 2, 3, //
+            3, 3, 3, 3, 3,
+        ];
+        assert_eq!(trap_2d(&terrain2, 5, 5), 10);
+
+        let terrain3 = [
+            -3, -3, -3, -3, //
+            -3, -9, -3, -3, //
+            -3, -3, -5, -3, //
+            -3, -3, -3, -3,
+        ];
+        assert_eq!(trap_2d(&terrain3, 4, 4), 8);
+
+        // the lowest spot of the border lets the inner pool drain
+        let terrain4 = [
+            5, 5, 5, //
+            5, 1, 5, //
+            5, 2, 5,
+        ];
+        assert_eq!(trap_2d(&terrain4, 3, 3), 1);
+        assert_eq!(
+            water_levels_2d(&terrain4, 3, 3),
+            vec![5, 5, 5, 5, 2, 5, 5, 2, 5]
+        );
+
+        assert_eq!(trap_2d(&[], 0, 0), 0);
+        assert_eq!(trap_2d(&[], 0, 3), 0);
+        assert_eq!(trap_2d(&[7], 1, 1), 0);
+    }
+
+    #[test]
+    fn test_trap_2d_degenerate() {
+        for terrain in TERRAINS {
+            let n = terrain.len();
+            assert_eq!(trap_2d(terrain, n, 1), compute_rain_collected(terrain));
+            assert_eq!(trap_2d(terrain, 1, n), compute_rain_collected(terrain));
+        }
+    }
+
+    #[test]
+    fn test_trap_2d_extreme() {
+        let terrain = [i64::MAX, i64::MIN, i64::MAX];
+        assert_eq!(trap_2d(&terrain, 3, 1), u64::MAX);
+        assert_eq!(trap_2d(&terrain, 1, 3), u64::MAX);
+        assert_eq!(compute_rain_collected(&terrain), u64::MAX);
+        let bowl = [
+            i64::MAX,
+            i64::MAX,
+            i64::MAX, //
+            i64::MAX,
+            i64::MIN,
+            i64::MAX, //
+            i64::MAX,
+            i64::MAX,
+            i64::MAX,
+        ];
+        assert_eq!(trap_2d(&bowl, 3, 3), u64::MAX);
+        let bowl = [
+            i64::MAX,
+            i64::MAX,
+            i64::MAX, //
+            0,
+            i64::MIN,
+            i64::MAX, //
+            i64::MAX,
+            i64::MAX,
+            i64::MAX,
+        ];
+        assert_eq!(trap_2d(&bowl, 3, 3), 1 << 63);
+
+        let spots = [Some(i64::MAX), Some(i64::MIN), Some(i64::MAX)];
+        assert_eq!(trap_2d_with_drains(&spots, 3, 1), u64::MAX);
+        let spots = [Some(0), Some(i64::MIN), Some(i64::MAX), None];
+        assert_eq!(trap_2d_with_drains(&spots, 4, 1), 1 << 63);
+    }
+
+    #[test]
+    fn test_trap_2d_with_drains() {
+        let terrain = [
+            Some(5),
+            Some(5),
+            Some(5),
+            Some(5),
+            Some(5), //
+            Some(5),
+            Some(1),
+            Some(3),
+            Some(2),
+            Some(5), //
+            Some(5),
+            Some(4),
+            Some(4),
+            None,
+            Some(5), //
+            Some(5),
+            Some(5),
+            Some(5),
+            Some(5),
+            Some(5),
+        ];
+        // the spot of elevation 1 drains down to 3, the others are dry
+        assert_eq!(trap_2d_with_drains(&terrain, 5, 4), 2);
+        // without the drain, the inner spots fill up to 5
+        let mut closed = terrain;
+        closed[13] = Some(0);
+        assert_eq!(trap_2d_with_drains(&closed, 5, 4), 16);
+        let heights: Vec<i64> = closed.iter().flatten().copied().collect();
+        assert_eq!(trap_2d(&heights, 5, 4), 16);
+
+        assert_eq!(trap_2d_with_drains(&[None], 1, 1), 0);
+        assert_eq!(trap_2d_with_drains(&[], 0, 0), 0);
+    }
+
+    #[test]
+    fn test_trap_2d_degenerate_with_drains() {
+        for terrain in TERRAINS.into_iter().filter(|t| !t.is_empty()) {
+            let n = terrain.len();
+            let spots: Vec<Option<i64>> =
+                terrain.iter().copied().map(Some).collect();
+            // drains at the edges and in the middle
+            for drains in [vec![0], vec![n - 1], vec![n / 2], vec![0, n / 2]] {
+                let mut drained = spots.clone();
+                drains.iter().for_each(|&i| drained[i] = None);
+                let water = trap_with_drains(&drained);
+                assert_eq!(trap_2d_with_drains(&drained, n, 1), water);
+                assert_eq!(trap_2d_with_drains(&drained, 1, n), water);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_trap_2d_size_mismatch() {
+        trap_2d(&[1, 2, 3], 2, 2);
+    }
+}
@@ -0,0 +1,234 @@
+//! # Subrange queries
+//! Preprocess a terrain once to answer how much rain any window of it
+//! would trap on its own in logarithmic time.
+
+/// A terrain preprocessed for queries of the rain trapped in its windows.
+///
+/// A window `[l, r)` splits at its maximum elevation into a stair going up
+/// from the left and a stair going down to the right, see
+/// [`compute_rain_collected`](crate::compute_rain_collected). The steps of
+/// the stair going up from `l` are the elevations of the chain of spots
+/// reached by repeatedly jumping to the next strictly higher spot, and that
+/// chain passes through the leftmost maximum of the window. So the sum of
+/// the steps from `l` up to the maximum is the difference of two sums along
+/// that chain, which are precomputed for every spot. The same holds for the
+/// stair going down to the right, jumping to the previous strictly higher
+/// spot from `r - 1`. A segment tree finds the leftmost and the rightmost
+/// maximum of the window.
+///
+/// Memory use is linear in the length of the terrain.
+/// # Example
+/// ```
+/// use trap_rust::TerrainIndex;
+/// let terrain = [0, 1, -1, 2, 1, 0, 1, 3, 2, 1, 2, 1];
+/// let index = TerrainIndex::new(&terrain);
+/// assert_eq!(index.water_in(0, terrain.len()), 7);
+/// assert_eq!(index.water_in(3, 8), 4);
+/// assert_eq!(index.water_in(5, 8), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TerrainIndex {
+    height: Vec<i64>,
+    // segment tree of the leftmost and rightmost maximum, leaves from len()
+    maxima: Vec<(usize, usize)>,
+    // prefix[i]: sum of the elevations of the spots before i
+    prefix: Vec<i128>,
+    // forward[i]: sum of the steps of the stair going up from i to the end
+    forward: Vec<i128>,
+    // backward[i]: sum of the steps of the stair going up from i - 1 to 0
+    backward: Vec<i128>,
+}
+
+impl TerrainIndex {
+    /// Preprocess the terrain in `O(n)` time.
+    /// # Arguments
+    /// * `height` - A slice of integers representing the elevation of the
+    ///   terrain.
+    pub fn new(height: &[i64]) -> Self {
+        let n = height.len();
+
+        let mut maxima = vec![(0, 0); 2 * n];
+        for i in 0..n {
+            maxima[n + i] = (i, i);
+        }
+        for node in (1..n).rev() {
+            maxima[node] =
+                combine(height, maxima[2 * node], maxima[2 * node + 1]);
+        }
+
+        let mut prefix = vec![0i128; n + 1];
+        for i in 0..n {
+            prefix[i + 1] = prefix[i] + height[i] as i128;
+        }
+
+        // Next strictly higher spot on the right, n if there is none.
+        let mut forward = vec![0i128; n + 1];
+        let mut stack: Vec<usize> = Vec::new();
+        for i in (0..n).rev() {
+            while stack.last().is_some_and(|&j| height[j] <= height[i]) {
+                stack.pop();
+            }
+            let next = stack.last().copied().unwrap_or(n);
+            forward[i] = height[i] as i128 * (next - i) as i128 + forward[next];
+            stack.push(i);
+        }
+
+        // Previous strictly higher spot on the left, shifted by one so that
+        // 0 stands for none.
+        let mut backward = vec![0i128; n + 1];
+        stack.clear();
+        for i in 0..n {
+            while stack.last().is_some_and(|&j| height[j] <= height[i]) {
+                stack.pop();
+            }
+            let previous = stack.last().map_or(0, |&j| j + 1);
+            backward[i + 1] = height[i] as i128 * (i + 1 - previous) as i128
+                + backward[previous];
+            stack.push(i);
+        }
+
+        TerrainIndex {
+            height: height.to_vec(),
+            maxima,
+            prefix,
+            forward,
+            backward,
+        }
+    }
+
+    /// The number of spots of the terrain.
+    pub fn len(&self) -> usize {
+        self.height.len()
+    }
+
+    /// Whether the terrain has no spots.
+    pub fn is_empty(&self) -> bool {
+        self.height.is_empty()
+    }
+
+    /// Compute the amount of rain trapped in the window `[l, r)` of the
+    /// terrain on its own, in `O(log n)` time.
+    /// # Arguments
+    /// * `l` - The index of the first spot of the window.
+    /// * `r` - The index one past the last spot of the window.
+    /// # Returns
+    /// The amount of rain trapped in `&terrain[l..r]`.
+    /// # Panics
+    /// Panics if `l > r` or `r` is greater than the length of the terrain.
+    pub fn water_in(&self, l: usize, r: usize) -> u64 {
+        assert!(
+            l <= r && r <= self.len(),
+            "window {}..{} out of range for terrain of length {}",
+            l,
+            r,
+            self.len()
+        );
+        if l == r {
+            return 0;
+        }
+
+        let (left_max, right_max) = self.maxima_in(l, r);
+        let max = self.height[left_max] as i128;
+        let steps = (self.forward[l] - self.forward[left_max])
+            + max * (right_max - left_max + 1) as i128
+            + (self.backward[r] - self.backward[right_max + 1]);
+        (steps - (self.prefix[r] - self.prefix[l])) as u64
+    }
+
+    /// Leftmost and rightmost maximum of the non-empty window `[l, r)`.
+    pub(crate) fn maxima_in(&self, l: usize, r: usize) -> (usize, usize) {
+        let n = self.len();
+        let (mut l, mut r) = (l + n, r + n);
+        let (mut from_left, mut from_right) = (None, None);
+        let merge =
+            |a: Option<(usize, usize)>, b: Option<(usize, usize)>| match (a, b)
+            {
+                (Some(a), Some(b)) => Some(combine(&self.height, a, b)),
+                (a, None) => a,
+                (None, b) => b,
+            };
+        while l < r {
+            if l % 2 == 1 {
+                from_left = merge(from_left, Some(self.maxima[l]));
+                l += 1;
+            }
+            if r % 2 == 1 {
+                r -= 1;
+                from_right = merge(Some(self.maxima[r]), from_right);
+            }
+            l /= 2;
+            r /= 2;
+        }
+        merge(from_left, from_right).unwrap() // the window isn't empty
+    }
+}
+
+/// Leftmost and rightmost maximum of two adjacent windows, `a` on the left.
+fn combine(
+    height: &[i64],
+    a: (usize, usize),
+    b: (usize, usize),
+) -> (usize, usize) {
+    let leftmost = if height[a.0] >= height[b.0] { a.0 } else { b.0 };
+    let rightmost = if height[b.1] >= height[a.1] { b.1 } else { a.1 };
+    (leftmost, rightmost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_rain_collected;
+    use crate::test_util::{random, TERRAINS};
+
+    fn check(terrain: &[i64]) {
+        let index = TerrainIndex::new(terrain);
+        for l in 0..=terrain.len() {
+            for r in l..=terrain.len() {
+                assert_eq!(
+                    index.water_in(l, r),
+                    compute_rain_collected(&terrain[l..r]),
+                    "window {}..{} of {:?}",
+                    l,
+                    r,
+                    terrain
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_water_in() {
+        for terrain in TERRAINS {
+            check(terrain);
+        }
+    }
+
+    #[test]
+    fn test_water_in_random() {
+        let mut state = 0x9E3779B97F4A7C15u64;
+        for n in [3, 17, 64, 100] {
+            for range in [3, 10, 1000] {
+                let terrain: Vec<i64> = (0..n)
+                    .map(|_| {
+                        (random(&mut state) % range) as i64 - range as i64 / 2
+                    })
+                    .collect();
+                check(&terrain);
+            }
+        }
+    }
+
+    #[test]
+    fn test_water_in_extreme() {
+        let terrain = [i64::MAX, i64::MIN, 0, i64::MIN, i64::MAX, 0];
+        let index = TerrainIndex::new(&terrain);
+        assert_eq!(index.water_in(0, 2), 0);
+        assert_eq!(index.water_in(1, 5), i64::MAX as u64 + 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_water_in_out_of_range() {
+        TerrainIndex::new(&[1, 2, 3]).water_in(1, 4);
+    }
+}
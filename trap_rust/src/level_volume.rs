@@ -0,0 +1,206 @@
+//! # Level-volume curves
+//! How much of the trapped rain lies below a given water surface level,
+//! for the whole terrain and for each of its pools.
+
+use crate::{pools, water_levels, Pool};
+
+/// Compute the amount of rain trapped in the terrain if the water surface
+/// can't rise above `level`.
+/// # Arguments
+/// * `height` - A slice of integers representing the elevation of the terrain.
+/// * `level` - The highest possible water surface, `i64::MAX` for no limit.
+/// # Returns
+/// The amount of rain trapped in the terrain below `level`.
+/// # Example
+/// ```
+/// use trap_rust::volume_below_level;
+/// let terrain = [5, 4, 2, 6, 6, 6, 4, 5];
+/// assert_eq!(volume_below_level(&terrain, 3), 1);
+/// assert_eq!(volume_below_level(&terrain, 4), 2);
+/// assert_eq!(volume_below_level(&terrain, i64::MAX), 5);
+/// ```
+/// # Note
+/// The water on a spot is capped by `level` in addition to the lower of the
+/// maximum elevations on its left and on its right.
+pub fn volume_below_level(height: &[i64], level: i64) -> u64 {
+    water_levels(height)
+        .iter()
+        .zip(height)
+        .map(|(&surface, &h)| {
+            (surface.min(level).max(h)).wrapping_sub(h) as u64
+        })
+        .sum()
+}
+
+/// The amount of rain trapped in a terrain below any water surface level,
+/// precomputed to answer each level in `O(log n)`.
+///
+/// A wet spot of elevation `h` with its water surface at `s` holds
+/// `min(L, s) - h` below level `L > h`, which is `L - h` minus `L - s` if
+/// `L > s`. So the volume below `L` is found with the counts and sums of
+/// the elevations and water surfaces below `L`, read from sorted arrays with
+/// prefix sums. All spots of a pool share the same water surface, so each
+/// pool only needs the sorted elevations of its spots.
+/// # Example
+/// ```
+/// use trap_rust::LevelVolumeCurve;
+/// let terrain = [5, 4, 2, 6, 6, 6, 4, 5];
+/// let curve = LevelVolumeCurve::new(&terrain);
+/// assert_eq!(curve.volume_below(4), 2);
+/// assert_eq!(curve.volume_below(i64::MAX), 5);
+/// assert_eq!(curve.pools().len(), 2);
+/// assert_eq!(curve.pool_volume_below(0, 4), 2);
+/// assert_eq!(curve.pool_volume_below(1, 4), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LevelVolumeCurve {
+    // elevations of the wet spots, sorted by pool and within each pool
+    pool_heights: SortedSums,
+    // elevations of all wet spots
+    heights: SortedSums,
+    // water surfaces of all wet spots
+    surfaces: SortedSums,
+    pools: Vec<Pool>,
+    // pool_start[p]..pool_start[p + 1] are the spots of pool p in
+    // pool_heights
+    pool_start: Vec<usize>,
+}
+
+impl LevelVolumeCurve {
+    /// Precompute the curve of the terrain in `O(n log n)`.
+    /// # Arguments
+    /// * `height` - A slice of integers representing the elevation of the
+    ///   terrain.
+    pub fn new(height: &[i64]) -> Self {
+        let pools = pools(height);
+        let mut pool_start = vec![0];
+        let mut pool_heights = Vec::new();
+        let mut surfaces = Vec::new();
+        for pool in &pools {
+            let start = pool_heights.len();
+            pool_heights.extend_from_slice(&height[pool.start..pool.end]);
+            pool_heights[start..].sort_unstable();
+            pool_start.push(pool_heights.len());
+            surfaces
+                .extend(std::iter::repeat_n(pool.level, pool.end - pool.start));
+        }
+        let mut heights = pool_heights.clone();
+        heights.sort_unstable();
+        LevelVolumeCurve {
+            pool_heights: SortedSums::new(pool_heights),
+            heights: SortedSums::new(heights),
+            surfaces: SortedSums::new(surfaces),
+            pools,
+            pool_start,
+        }
+    }
+
+    /// The pools of the terrain, see [`pools`](crate::pools).
+    pub fn pools(&self) -> &[Pool] {
+        &self.pools
+    }
+
+    /// The amount of rain trapped in the terrain if the water surface can't
+    /// rise above `level`, `i64::MAX` for no limit.
+    pub fn volume_below(&self, level: i64) -> u64 {
+        let n = self.heights.values.len();
+        let wet = self.heights.below(0, n, level);
+        let full = self.surfaces.below(0, n, level);
+        (wet - full) as u64
+    }
+
+    /// The amount of rain trapped in pool `pool` of [`pools`](Self::pools)
+    /// if its water surface can't rise above `level`.
+    /// # Panics
+    /// Panics if there is no pool `pool`.
+    pub fn pool_volume_below(&self, pool: usize, level: i64) -> u64 {
+        let level = level.min(self.pools[pool].level);
+        let (start, end) = (self.pool_start[pool], self.pool_start[pool + 1]);
+        self.pool_heights.below(start, end, level) as u64
+    }
+}
+
+/// Sorted runs of values with their prefix sums.
+#[derive(Debug, Clone)]
+struct SortedSums {
+    values: Vec<i64>,
+    // sum[i]: sum of values[..i]
+    sum: Vec<i128>,
+}
+
+impl SortedSums {
+    fn new(values: Vec<i64>) -> Self {
+        let mut sum = vec![0i128];
+        for &x in &values {
+            sum.push(sum.last().unwrap() + x as i128);
+        }
+        SortedSums { values, sum }
+    }
+
+    /// Sum of `level - x` over the values `x < level` of the sorted run
+    /// `start..end`.
+    fn below(&self, start: usize, end: usize, level: i64) -> i128 {
+        let i = start + self.values[start..end].partition_point(|&x| x < level);
+        level as i128 * (i - start) as i128 - (self.sum[i] - self.sum[start])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_rain_collected;
+    use crate::test_util::TERRAINS;
+
+    #[test]
+    fn test_level_volume_curve() {
+        for terrain in TERRAINS {
+            let curve = LevelVolumeCurve::new(terrain);
+            let water = compute_rain_collected(terrain);
+            assert_eq!(volume_below_level(terrain, i64::MAX), water);
+            assert_eq!(curve.volume_below(i64::MAX), water);
+            assert_eq!(volume_below_level(terrain, i64::MIN), 0);
+            assert_eq!(curve.volume_below(i64::MIN), 0);
+            for level in -12..12 {
+                let volume = volume_below_level(terrain, level);
+                assert_eq!(curve.volume_below(level), volume);
+                let per_pool: u64 = (0..curve.pools().len())
+                    .map(|p| curve.pool_volume_below(p, level))
+                    .sum();
+                assert_eq!(per_pool, volume);
+            }
+            for (p, pool) in curve.pools().iter().enumerate() {
+                assert_eq!(curve.pool_volume_below(p, i64::MAX), pool.volume);
+                assert_eq!(curve.pool_volume_below(p, pool.level), pool.volume);
+            }
+        }
+    }
+
+    #[test]
+    fn test_volume_below_level() {
+        let terrain = [0, 1, -1, 2, 1, 0, 1, 3, 2, 1, 2, 1];
+        // water surfaces 1 on spot 2, 2 on spots 4 to 6 and on spot 9
+        let volumes: Vec<u64> = (-1..=3)
+            .map(|level| volume_below_level(&terrain, level))
+            .collect();
+        assert_eq!(volumes, [0, 1, 3, 7, 7]);
+    }
+
+    #[test]
+    fn test_volume_below_level_extreme() {
+        let terrain = [i64::MAX, 0, 0, i64::MAX];
+        let curve = LevelVolumeCurve::new(&terrain);
+        assert_eq!(curve.volume_below(i64::MAX), u64::MAX - 1);
+        assert_eq!(curve.volume_below(1 << 62), 1 << 63);
+        assert_eq!(curve.pool_volume_below(0, 1 << 62), 1 << 63);
+        let terrain = [i64::MAX, i64::MIN, i64::MAX];
+        assert_eq!(volume_below_level(&terrain, i64::MAX), u64::MAX);
+        assert_eq!(volume_below_level(&terrain, 0), 1 << 63);
+        let curve = LevelVolumeCurve::new(&terrain);
+        assert_eq!(curve.pools()[0].volume, u64::MAX);
+        assert_eq!(curve.volume_below(i64::MAX), u64::MAX);
+        assert_eq!(curve.volume_below(0), 1 << 63);
+        assert_eq!(curve.volume_below(i64::MIN), 0);
+        assert_eq!(curve.pool_volume_below(0, i64::MAX), u64::MAX);
+        assert_eq!(curve.pool_volume_below(0, -1), (1 << 63) - 1);
+    }
+}
@@ -0,0 +1,187 @@
+//! # Per-spot water levels
+//! Instead of the total amount of rain trapped, report where the water
+//! sits: the elevation of the water surface and the depth of the water
+//! for every spot of the terrain.
+
+/// Lazy iterator over the water depth of each spot of a terrain.
+///
+/// Yields `(index, water_depth)` pairs. The spots are visited in the
+/// order used by [`compute_rain_collected`](crate::compute_rain_collected),
+/// i.e. taken from both ends of the terrain on a minimum first basis, so
+/// the indices are not in ascending order: the spot with the lower
+/// elevation of the leftmost and the rightmost spot not yet visited comes
+/// next, the leftmost one on ties. So the indices from the left end come
+/// in ascending and those from the right end in descending order. Every
+/// index of the terrain is yielded exactly once. For the depths in the
+/// order of the terrain, see [`water_levels`].
+#[derive(Debug, Clone)]
+pub struct WaterDepths<'a> {
+    height: &'a [i64],
+    left: usize,
+    right: usize, // one past the rightmost spot not yet visited
+    stepsize: i64,
+}
+
+impl<'a> WaterDepths<'a> {
+    /// Create the iterator for the given terrain.
+    pub fn new(height: &'a [i64]) -> Self {
+        WaterDepths {
+            height,
+            left: 0,
+            right: height.len(),
+            stepsize: i64::MIN,
+        }
+    }
+
+    /// Advance the iterator, yielding the index of the visited spot and
+    /// the elevation of the step of the stair at that spot.
+    pub(crate) fn next_step(&mut self) -> Option<(usize, i64)> {
+        if self.left >= self.right {
+            return None;
+        }
+        let (l, r) = (self.height[self.left], self.height[self.right - 1]);
+        let index = if l <= r {
+            self.left += 1;
+            self.left - 1
+        } else {
+            self.right -= 1;
+            self.right
+        };
+        self.stepsize = self.stepsize.max(self.height[index]);
+        Some((index, self.stepsize))
+    }
+}
+
+impl Iterator for WaterDepths<'_> {
+    type Item = (usize, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The difference always fits into a u64, even if it overflows i64.
+        self.next_step().map(|(i, stepsize)| {
+            (i, stepsize.wrapping_sub(self.height[i]) as u64)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.right - self.left;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for WaterDepths<'_> {}
+
+impl std::iter::FusedIterator for WaterDepths<'_> {}
+
+/// Lazily compute the water depth of each spot of the terrain.
+/// # Arguments
+/// * `height` - A slice of integers representing the elevation of the terrain.
+/// # Returns
+/// An iterator yielding `(index, water_depth)` for every spot of the terrain,
+/// see [`WaterDepths`] for the order of the spots.
+/// # Example
+/// ```
+/// use trap_rust::water_depths;
+/// let terrain = [2, 1, 3, 0, 2];
+/// let mut depths: Vec<_> = water_depths(&terrain).collect();
+/// depths.sort();
+/// assert_eq!(depths, vec![(0, 0), (1, 1), (2, 0), (3, 2), (4, 0)]);
+/// ```
+pub fn water_depths(height: &[i64]) -> WaterDepths<'_> {
+    WaterDepths::new(height)
+}
+
+/// Compute the elevation of the water surface for each spot of the terrain.
+/// # Arguments
+/// * `height` - A slice of integers representing the elevation of the terrain.
+/// # Returns
+/// A vector holding for each spot the elevation of the water surface, which
+/// equals the elevation of the spot if no water is trapped there.
+/// # Example
+/// ```
+/// use trap_rust::water_levels;
+/// let terrain = [2, 1, 3, 0, 2];
+/// assert_eq!(water_levels(&terrain), vec![2, 2, 3, 2, 2]);
+/// ```
+/// # Note
+/// The water levels are the steps of the stair described in
+/// [`compute_rain_collected`](crate::compute_rain_collected), so the
+/// difference between the water levels and the terrain sums up to the
+/// amount of rain trapped.
+pub fn water_levels(height: &[i64]) -> Vec<i64> {
+    let mut levels = vec![0; height.len()];
+    let mut steps = WaterDepths::new(height);
+    while let Some((i, stepsize)) = steps.next_step() {
+        levels[i] = stepsize;
+    }
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_rain_collected;
+    use crate::test_util::TERRAINS;
+
+    #[test]
+    fn test_water_levels() {
+        assert_eq!(water_levels(&[]), vec![]);
+        assert_eq!(water_levels(&[3]), vec![3]);
+        assert_eq!(water_levels(&[2, 1, 2]), vec![2, 2, 2]);
+        assert_eq!(water_levels(&[1, 2, 3, 2, 1]), vec![1, 2, 3, 2, 1]);
+        assert_eq!(
+            water_levels(&[5, 4, 2, 6, 6, 6, 4, 5]),
+            vec![5, 5, 5, 6, 6, 6, 5, 5]
+        );
+        assert_eq!(
+            water_levels(&[0, -6, 0, -2, 8, -9, 0, 8, 9, -5]),
+            vec![0, 0, 0, 0, 8, 8, 8, 8, 9, -5]
+        );
+    }
+
+    #[test]
+    fn test_water_depths() {
+        for terrain in TERRAINS {
+            let mut depths: Vec<_> = water_depths(terrain).collect();
+            assert_eq!(depths.len(), terrain.len());
+            assert_eq!(
+                depths.iter().map(|&(_, d)| d).sum::<u64>(),
+                compute_rain_collected(terrain)
+            );
+            depths.sort();
+            let levels = water_levels(terrain);
+            for (i, d) in depths.into_iter().enumerate() {
+                assert_eq!(d, (i, levels[i].wrapping_sub(terrain[i]) as u64));
+            }
+        }
+    }
+
+    #[test]
+    fn test_water_depths_extreme() {
+        let terrain = [i64::MAX, i64::MIN, i64::MAX];
+        let depths: Vec<_> = water_depths(&terrain).collect();
+        assert_eq!(depths, vec![(0, 0), (1, u64::MAX), (2, 0)]);
+        let terrain = [i64::MIN, i64::MAX, i64::MIN, 0, i64::MIN];
+        let depths: Vec<_> = water_depths(&terrain).collect();
+        assert_eq!(depths, vec![(0, 0), (4, 0), (3, 0), (2, 1 << 63), (1, 0)]);
+    }
+
+    #[test]
+    fn test_water_depths_order() {
+        let depths: Vec<_> = water_depths(&[2, 1, 3, 0, 2]).collect();
+        assert_eq!(depths, vec![(0, 0), (1, 1), (4, 0), (3, 2), (2, 0)]);
+        for terrain in TERRAINS {
+            // the lower end comes first, the left one on ties
+            let (mut left, mut right) = (0, terrain.len());
+            for (i, _) in water_depths(terrain) {
+                if terrain[left] <= terrain[right - 1] {
+                    assert_eq!(i, left);
+                    left += 1;
+                } else {
+                    right -= 1;
+                    assert_eq!(i, right);
+                }
+            }
+            assert_eq!(left, right);
+        }
+    }
+}
@@ -3,10 +3,67 @@
 //! representing the elevation of a spot, calculate the amount of
 //! rain that can be trapped in the terrain.
 
+mod boundary;
+mod ceiling;
+mod checked;
+mod circular;
+mod curve;
+mod drains;
+mod dynamic;
+mod file;
+mod float;
+pub mod generic;
+pub mod grid;
+mod index;
+mod level_volume;
+mod levels;
+mod mapped;
+mod parallel;
+mod pools;
+mod pour;
+mod rainfall;
+mod simd;
+mod stream;
+mod summary;
+#[cfg(test)]
+mod test_util;
+mod wide;
+
+pub use boundary::{trap_with_boundaries, Boundary};
+pub use ceiling::trap_with_ceiling;
+pub use checked::{
+    try_compute_rain_collected, try_compute_rain_collected3, try_trap,
+    TrapError,
+};
+pub use circular::{trap_circular, trap_circular_with_outlet};
+pub use curve::{fill_curve, FillCurve};
+pub use drains::trap_with_drains;
+pub use dynamic::DynamicTerrain;
+pub use file::{compute_rain_collected_file, compute_rain_collected_reader};
+pub use float::{
+    compute_rain_collected_f32, compute_rain_collected_f64, trap_f32, trap_f64,
+    NanError, NanPolicy,
+};
+pub use generic::Elevation;
+pub use index::TerrainIndex;
+pub use level_volume::{volume_below_level, LevelVolumeCurve};
+pub use levels::{water_depths, water_levels, WaterDepths};
+pub use mapped::MappedTerrain;
+pub use parallel::compute_rain_collected_parallel;
+pub use pools::{pools, Pool};
+pub use pour::pour;
+pub use rainfall::simulate_rain;
+pub use simd::trap_simd;
+pub use stream::RainAccumulator;
+pub use summary::{ChunkSummary, SummaryError};
+pub use wide::{
+    compute_rain_collected_wide, compute_rain_collected_wide_ffi, WideVolume,
+};
+
 /// C interface to compute_rain_collected
 /// # Safety
 /// The caller must ensure that the pointer is valid and points to a valid
-/// slice of integers.
+/// slice of integers. If `len` is zero, the pointer may be null.
 /// # Arguments
 /// * `height` - A pointer to a slice of integers representing the elevation of the terrain.
 /// * `len` - The length of the slice.
@@ -32,6 +89,10 @@ pub unsafe extern "C" fn compute_rain_collected_ffi(
     height: *const i64,
     len: usize,
 ) -> u64 {
+    if len == 0 {
+        // height may be null for an empty terrain
+        return 0;
+    }
     let slice = std::slice::from_raw_parts(height, len);
     compute_rain_collected(slice)
 }
@@ -58,43 +119,33 @@ pub unsafe extern "C" fn compute_rain_collected_ffi(
 /// is the last spot of the terrain, we can calculate the water collected by iterating
 /// the terrain from left to right and applying a fold operation that keeps track of the
 /// stepsize and the water collected.
+///
+/// Water flows off both ends of the terrain, see
+/// [`trap_with_boundaries`] for walls or lakes beyond them.
 pub fn compute_rain_collected(height: &[i64]) -> u64 {
-    let mut height = height.into_iter().copied();
-
-    std::iter::repeat(())
-        // We reorder the sequence of elevations by taking values
-        // from both ends of the terrain on a minimum first basis,
-        // advancing the iterator that points to the smaller value.
-        // This way we are guaranteed to have the maximum elevation
-        // as the last spot.
-        .scan((height.next(), height.next_back()), |state, _| {
-            if let (Some(left), Some(right)) = *state {
-                if left <= right {
-                    *state = (height.next(), Some(right));
-                    Some(left)
-                } else {
-                    *state = (Some(left), height.next_back());
-                    Some(right)
-                }
-            } else {
-                None
-            }
-        })
-        // The newly ordered sequence traps the same amount of
-        // water as the former one (yes, you got it, proof is left
-        // to the reader). We can now apply the fold operation
-        // described above for the "maximum is last" case.
-        .fold(
-            (
-                i64::MIN, // keeps track of the stepsize of the stair
-                0u64,     // keeps track of the water collected
-            ),
-            |acc, x| {
-                let stepsize = x.max(acc.0);
-                (stepsize, acc.1 + (stepsize - x) as u64)
-            },
-        )
-        .1 // we are only interested in the water collected
+    generic::compute_rain_collected(height)
+}
+
+/// Variant of compute_rain_collected consuming an iterator of ints as input.
+/// # Arguments
+/// * `height` - The elevations of the terrain, in any collection or iterator
+///   that can be read from both ends.
+/// # Returns
+/// The amount of rain that can be trapped in the terrain.
+/// # Example
+/// ```
+/// use std::collections::VecDeque;
+/// use trap_rust::compute_rain_collected_iter;
+/// let terrain = VecDeque::from([1, 4, 2, 5, 3, 6, 4, 7]);
+/// let water_capacity = compute_rain_collected_iter(terrain);
+/// assert_eq!(water_capacity, 6);
+/// ```
+pub fn compute_rain_collected_iter<I>(height: I) -> u64
+where
+    I: IntoIterator<Item = i64>,
+    I::IntoIter: DoubleEndedIterator,
+{
+    generic::compute_rain_collected_iter(height)
 }
 
 /// Variant of compute_rain_collected consuming a vector of ints as input.
@@ -115,121 +166,22 @@ pub fn compute_rain_collected_v(height: Vec<i64>) -> u64 {
 
 /// previous solution
 pub fn compute_rain_collected3(terrain: &[i64]) -> u64 {
-    let n = terrain.len();
-    if n < 3 {
-        return 0;
-    }
-
-    // Water + terrain create a stair up left to the maximum elevation and a
-    // stair down right to the maximum elevation. The elevation of each step of
-    // the stair is the maximum elevation seen so far walking towards the
-    // maximum elevation from either side. Water collected is determined by the
-    // difference between the elevation of the current step and the actual
-    // elevation.
-
-    let index_maximum = terrain
-        .iter()
-        .enumerate()
-        .max_by_key(|&(_, v)| v)
-        .unwrap() // since we ensured above that terrain isn't empty, this is safe
-        .0;
-
-    let (terrain_left_of_max_elevation, terrain_right_of_max_elevation) =
-        terrain.split_at(index_maximum);
-
-    // We calculate the water collected on the left side of the maximum elevation.
-    let water_capacity_left = terrain_left_of_max_elevation
-        .iter()
-        .fold(
-            (
-                i64::MIN, // .0: tracks maximum elevation
-                0u64,     // .1: tracks water collected
-            ),
-            |acc, &x| {
-                let stepsize = x.max(acc.0); // update the maximum elevation seen so far
-                (stepsize, acc.1 + (stepsize - x) as u64) // stepsize will always be greater as or equal to x,
-                                                          // which makes the cast safe
-            },
-        )
-        .1; // we are only interested in the water collected
-
-    // This time we calculate the water collected on the right side of the
-    // maximum elevation. We do this by iterating the terrain right to the max
-    // elevation applying the same fold operation in reverse order.
-    let water_capacity_right = terrain_right_of_max_elevation
-        .iter()
-        .rfold((i64::MIN, 0u64), |acc, &x| {
-            let stepsize = x.max(acc.0);
-            (stepsize, acc.1 + (stepsize - x) as u64)
-        })
-        .1;
-
-    water_capacity_left + water_capacity_right
+    generic::compute_rain_collected3(terrain)
 }
 
 /// fastest solution from leetcode
 pub fn trap(height: &[i64]) -> u64 {
-    let (mut left, mut right) = (0, height.len() - 1);
-    let mut pool_height = i64::MIN;
-    let mut trapped = 0u64;
-
-    while left < right {
-        pool_height = pool_height.max(height[left].min(height[right]));
-        if height[left] <= height[right] {
-            trapped += 0.max(pool_height - height[left]) as u64;
-            left += 1;
-        } else {
-            trapped += 0.max(pool_height - height[right]) as u64;
-            right -= 1;
-        }
-    }
-
-    trapped
+    generic::trap(height)
 }
 
 /// fastest solution from leetcode, consuming input
 pub fn trap_v(height: Vec<i64>) -> u64 {
-    let (mut left, mut right) = (0, height.len() - 1);
-    let mut pool_height = i64::MIN;
-    let mut trapped = 0u64;
-
-    while left < right {
-        pool_height = pool_height.max(height[left].min(height[right]));
-        if height[left] <= height[right] {
-            trapped += 0.max(pool_height - height[left]) as u64;
-            left += 1;
-        } else {
-            trapped += 0.max(pool_height - height[right]) as u64;
-            right -= 1;
-        }
-    }
-
-    trapped
+    generic::trap_v(height)
 }
 
-#[allow(unsafe_code)]
 /// fastest solution from leetcode, avoiding bounds checks
 pub fn trap_unsafe(height: &[i64]) -> u64 {
-    let (mut left, mut right) = (0, height.len() - 1);
-    let mut pool_height = i64::MIN;
-    let mut trapped = 0u64;
-
-    while left < right {
-        unsafe {
-            let leftv = *height.get_unchecked(left);
-            let rightv = *height.get_unchecked(right);
-            pool_height = pool_height.max(leftv.min(rightv));
-            if leftv <= rightv {
-                trapped += 0.max(pool_height - leftv) as u64;
-                left += 1;
-            } else {
-                trapped += 0.max(pool_height - rightv) as u64;
-                right -= 1;
-            }
-        }
-    }
-
-    trapped
+    generic::trap_unsafe(height)
 }
 
 #[cfg(test)]
@@ -451,4 +403,57 @@ mod tests {
         let terrain11 = vec![0, -6, 0, -2, 8, -9, 0, 8, 9, -5];
         assert_eq!(trap_v(terrain11), 33);
     }
+
+    #[test]
+    fn test_compute_rain_collected_iter() {
+        use std::collections::VecDeque;
+
+        let terrain1 = VecDeque::from([1, 4, 2, 5, 3, 6, 4, 7]);
+        assert_eq!(compute_rain_collected_iter(terrain1), 6);
+
+        let terrain2 = [0, -6, 0, -2, 8];
+        let terrain3 = [-9, 0, 8, 9, -5];
+        assert_eq!(
+            compute_rain_collected_iter(terrain2.into_iter().chain(terrain3)),
+            33
+        );
+
+        let terrain4 = [5u16, 4, 2, 6, 6, 6, 4, 5];
+        assert_eq!(
+            compute_rain_collected_iter(terrain4.iter().map(|&x| x as i64)),
+            5
+        );
+
+        let terrain5 = b"0 1 -1 2 1 0 1 3 2 1 2 1";
+        let decoded = std::str::from_utf8(terrain5)
+            .unwrap()
+            .split(' ')
+            .map(|x| x.parse().unwrap());
+        assert_eq!(compute_rain_collected_iter(decoded.rev()), 7);
+
+        assert_eq!(compute_rain_collected_iter(std::iter::empty()), 0);
+    }
+
+    #[test]
+    fn test_tiny_terrains() {
+        let terrains: [&[i64]; 5] = [&[], &[7], &[-3], &[1, 2], &[2, 1]];
+        for terrain in terrains {
+            assert_eq!(compute_rain_collected(terrain), 0);
+            assert_eq!(compute_rain_collected_v(terrain.to_vec()), 0);
+            assert_eq!(compute_rain_collected3(terrain), 0);
+            assert_eq!(trap(terrain), 0);
+            assert_eq!(trap_v(terrain.to_vec()), 0);
+            assert_eq!(trap_unsafe(terrain), 0);
+            assert_eq!(
+                unsafe {
+                    compute_rain_collected_ffi(terrain.as_ptr(), terrain.len())
+                },
+                0
+            );
+        }
+        assert_eq!(
+            unsafe { compute_rain_collected_ffi(std::ptr::null(), 0) },
+            0
+        );
+    }
 }
@@ -0,0 +1,241 @@
+//! # Memory-mapped terrains
+//! Run the algorithms of this crate directly on a terrain file of
+//! little-endian `i64` elevations, without copying it into a `Vec`.
+
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+/// A terrain file mapped read-only into memory.
+///
+/// The mapping dereferences to `&[i64]`, so it can be passed to every
+/// function taking a terrain, including the FFI backends. Pages of the file
+/// are loaded by the operating system as they are accessed.
+/// # Example
+/// ```no_run
+/// use trap_rust::{compute_rain_collected, MappedTerrain};
+/// let terrain = unsafe { MappedTerrain::open("terrain.bin") }.unwrap();
+/// println!("Water capacity: {}", compute_rain_collected(&terrain));
+/// ```
+/// # Note
+/// On platforms other than Unix the file is read into memory instead.
+#[derive(Debug)]
+pub struct MappedTerrain {
+    #[cfg(unix)]
+    map: unix::Map,
+    #[cfg(not(unix))]
+    terrain: Vec<i64>,
+}
+
+impl MappedTerrain {
+    /// Map the terrain file at `path` into memory.
+    /// # Arguments
+    /// * `path` - The path of a file of little-endian `i64` elevations.
+    /// # Returns
+    /// The mapped terrain, or an error if the file can't be mapped, its
+    /// length isn't a multiple of 8 bytes or the CPU isn't little-endian.
+    /// # Safety
+    /// The caller must guarantee that, for as long as the returned
+    /// `MappedTerrain` or any slice borrowed from it lives, no one, neither
+    /// this process nor any other, writes to the file or truncates it. On
+    /// Unix the file is mapped with `MAP_PRIVATE`, which doesn't guarantee
+    /// that changes made to the file after mapping it stay invisible: a
+    /// write changes the elevations under a shared `&[i64]`, which is
+    /// undefined behavior, and a truncation makes accessing the pages past
+    /// the new end of the file raise `SIGBUS`. On other platforms the file
+    /// is read into memory once, so there are no such requirements.
+    #[allow(unsafe_code)]
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        if cfg!(target_endian = "big") {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "terrain files are little-endian",
+            ));
+        }
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        if len % 8 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("terrain of {} bytes isn't a sequence of i64", len),
+            ));
+        }
+        let len = usize::try_from(len).map_err(|_| {
+            io::Error::new(io::ErrorKind::OutOfMemory, "terrain too large")
+        })?;
+
+        #[cfg(unix)]
+        {
+            Ok(MappedTerrain {
+                map: unsafe { unix::Map::new(&file, len)? },
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            use std::io::Read;
+            let mut bytes = Vec::with_capacity(len);
+            (&file).read_to_end(&mut bytes)?;
+            Ok(MappedTerrain {
+                terrain: bytes
+                    .chunks_exact(8)
+                    .map(|b| i64::from_le_bytes(b.try_into().unwrap()))
+                    .collect(),
+            })
+        }
+    }
+
+    /// The elevations of the terrain.
+    pub fn as_slice(&self) -> &[i64] {
+        #[cfg(unix)]
+        {
+            self.map.as_slice()
+        }
+        #[cfg(not(unix))]
+        {
+            &self.terrain
+        }
+    }
+}
+
+impl Deref for MappedTerrain {
+    type Target = [i64];
+
+    fn deref(&self) -> &[i64] {
+        self.as_slice()
+    }
+}
+
+impl AsRef<[i64]> for MappedTerrain {
+    fn as_ref(&self) -> &[i64] {
+        self.as_slice()
+    }
+}
+
+#[cfg(unix)]
+#[allow(unsafe_code)]
+mod unix {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    /// A read-only mapping of `len` bytes.
+    #[derive(Debug)]
+    pub(super) struct Map {
+        ptr: *const i64,
+        len: usize,
+    }
+
+    // The mapping is never written to.
+    unsafe impl Send for Map {}
+    unsafe impl Sync for Map {}
+
+    impl Map {
+        /// # Safety
+        /// `len` must be the length of the file, a multiple of 8.
+        pub(super) unsafe fn new(file: &File, len: usize) -> io::Result<Self> {
+            if len == 0 {
+                // mmap rejects empty mappings
+                return Ok(Map {
+                    ptr: std::ptr::NonNull::dangling().as_ptr(),
+                    len,
+                });
+            }
+            let ptr = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    libc::PROT_READ,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            let map = Map {
+                ptr: ptr.cast(),
+                len,
+            };
+            // mappings start at a page boundary
+            if !map.ptr.is_aligned() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "mapping isn't aligned for i64",
+                ));
+            }
+            Ok(map)
+        }
+
+        pub(super) fn as_slice(&self) -> &[i64] {
+            unsafe { std::slice::from_raw_parts(self.ptr, self.len / 8) }
+        }
+    }
+
+    impl Drop for Map {
+        fn drop(&mut self) {
+            if self.len > 0 {
+                unsafe {
+                    libc::munmap(self.ptr as *mut libc::c_void, self.len)
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(unsafe_code)]
+mod tests {
+    use super::*;
+    use crate::test_util::random;
+    use crate::{compute_rain_collected, compute_rain_collected_ffi, trap};
+
+    /// Write the terrain to a temporary file and map it.
+    fn mapped(name: &str, bytes: &[u8]) -> io::Result<MappedTerrain> {
+        let path = std::env::temp_dir().join(format!(
+            "trap_rust_{}_{}.bin",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, bytes).unwrap();
+        let terrain = unsafe { MappedTerrain::open(&path) };
+        std::fs::remove_file(&path).unwrap();
+        terrain
+    }
+
+    #[test]
+    fn test_mapped_terrain() {
+        let mut state = 0x3C6EF372FE94F82Bu64;
+        let terrain: Vec<i64> = (0..100_000)
+            .map(|_| (random(&mut state) % 100_000) as i64 - 50_000)
+            .collect();
+        let bytes: Vec<u8> =
+            terrain.iter().flat_map(|x| x.to_le_bytes()).collect();
+
+        let mapped = mapped("mapped", &bytes).unwrap();
+        assert_eq!(mapped.as_slice(), terrain.as_slice());
+        let water = compute_rain_collected(&terrain);
+        assert_eq!(compute_rain_collected(&mapped), water);
+        assert_eq!(trap(&mapped), water);
+        assert_eq!(
+            unsafe {
+                compute_rain_collected_ffi(mapped.as_ptr(), mapped.len())
+            },
+            water
+        );
+    }
+
+    #[test]
+    fn test_mapped_terrain_empty() {
+        let mapped = mapped("empty", &[]).unwrap();
+        assert!(mapped.is_empty());
+        assert_eq!(compute_rain_collected(&mapped), 0);
+    }
+
+    #[test]
+    fn test_mapped_terrain_invalid_length() {
+        let error = mapped("invalid", &[0; 12]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,160 @@
+//! # Parallel computation
+//! Multi-threaded variant of compute_rain_collected3 for very large
+//! terrains.
+
+use std::num::NonZeroUsize;
+use std::thread;
+
+/// Compute the amount of rain that can be trapped in the terrain using
+/// several threads.
+/// # Arguments
+/// * `terrain` - A slice of integers representing the elevation of the terrain.
+/// * `threads` - The number of threads to use, `0` for as many as there are
+///   cores available.
+/// # Returns
+/// The amount of rain that can be trapped in the terrain.
+/// # Example
+/// ```
+/// use trap_rust::compute_rain_collected_parallel;
+/// let terrain = [1, 4, 2, 5, 3, 6, 4, 7];
+/// assert_eq!(compute_rain_collected_parallel(&terrain, 3), 6);
+/// ```
+/// # Note
+/// Like compute_rain_collected3, the terrain is split at its maximum
+/// elevation into a stair going up from the left and a stair going down to
+/// the right. The terrain is cut into one chunk per thread. In a first pass,
+/// every thread finds the maximum of its chunk, which yields the global
+/// maximum and, for every chunk, the maximum of all chunks before and after
+/// it. In a second pass, every thread folds its chunk starting with these
+/// maxima as stepsize, left to right for the spots left of the global
+/// maximum and right to left for the spots right of it.
+pub fn compute_rain_collected_parallel(terrain: &[i64], threads: usize) -> u64 {
+    let n = terrain.len();
+    if n < 3 {
+        return 0;
+    }
+    let threads = match threads {
+        0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        threads => threads,
+    };
+    let chunk_size = n.div_ceil(threads);
+
+    // First pass: leftmost maximum of each chunk.
+    let maxima: Vec<(i64, usize)> = thread::scope(|s| {
+        terrain
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(c, chunk)| {
+                s.spawn(move || {
+                    let (i, &max) = chunk
+                        .iter()
+                        .enumerate()
+                        .rev() // max_by_key picks the last maximum
+                        .max_by_key(|&(_, v)| v)
+                        .unwrap(); // chunks aren't empty
+                    (max, c * chunk_size + i)
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    let index_maximum = maxima
+        .iter()
+        .rev()
+        .max_by_key(|&&(max, _)| max)
+        .unwrap() // terrain isn't empty
+        .1;
+
+    // Maximum of all chunks left and right of each chunk.
+    let before: Vec<i64> = maxima
+        .iter()
+        .scan(i64::MIN, |m, &(max, _)| {
+            Some(std::mem::replace(m, max.max(*m)))
+        })
+        .collect();
+    let mut after: Vec<i64> = maxima
+        .iter()
+        .rev()
+        .scan(i64::MIN, |m, &(max, _)| {
+            Some(std::mem::replace(m, max.max(*m)))
+        })
+        .collect();
+    after.reverse();
+
+    // Second pass: the stairs within each chunk.
+    thread::scope(|s| {
+        terrain
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(c, chunk)| {
+                let (before, after) = (before[c], after[c]);
+                s.spawn(move || {
+                    let start = c * chunk_size;
+                    let split = index_maximum.clamp(start, start + chunk.len());
+                    let (left, right) = chunk.split_at(split - start);
+
+                    let water_left = left
+                        .iter()
+                        .fold((before, 0u64), |acc, &x| {
+                            let stepsize = x.max(acc.0);
+                            // the difference fits into u64 even if it
+                            // overflows i64
+                            (stepsize, acc.1 + stepsize.wrapping_sub(x) as u64)
+                        })
+                        .1;
+                    let water_right = right
+                        .iter()
+                        .rfold((after, 0u64), |acc, &x| {
+                            let stepsize = x.max(acc.0);
+                            (stepsize, acc.1 + stepsize.wrapping_sub(x) as u64)
+                        })
+                        .1;
+                    water_left + water_right
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .sum()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_rain_collected;
+    use crate::test_util::{random, TERRAINS};
+
+    #[test]
+    fn test_compute_rain_collected_parallel() {
+        for terrain in TERRAINS {
+            for threads in 0..=16 {
+                assert_eq!(
+                    compute_rain_collected_parallel(terrain, threads),
+                    compute_rain_collected(terrain),
+                    "{} threads, {:?}",
+                    threads,
+                    terrain
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_compute_rain_collected_parallel_large() {
+        let mut state = 0x243F6A8885A308D3u64;
+        let terrain: Vec<i64> = (0..100_000)
+            .map(|_| (random(&mut state) % 100_000) as i64)
+            .collect();
+        let water = compute_rain_collected(&terrain);
+        for threads in [1, 2, 3, 4, 7, 8] {
+            assert_eq!(
+                compute_rain_collected_parallel(&terrain, threads),
+                water
+            );
+        }
+    }
+}
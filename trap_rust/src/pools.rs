@@ -0,0 +1,179 @@
+//! # Pools of trapped rain
+//! Split the water trapped in the terrain into its distinct pools.
+
+use crate::water_levels;
+
+/// A single pool of water trapped in the terrain.
+///
+/// A pool is a maximal run of consecutive spots covered by water. All spots
+/// of a pool share the same water surface level, and the pool is bounded by
+/// a dry spot on either side. Spots whose elevation equals the water level
+/// hold no water and therefore separate pools, so a flat plateau is never
+/// part of a pool, it is a wall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Pool {
+    /// Index of the first spot covered by water.
+    pub start: usize,
+    /// Index one past the last spot covered by water.
+    pub end: usize,
+    /// Index of the dry spot bounding the pool on the left, `start - 1`.
+    pub left_wall: usize,
+    /// Index of the dry spot bounding the pool on the right, `end`.
+    pub right_wall: usize,
+    /// Elevation of the water surface.
+    pub level: i64,
+    /// Depth of the water at the deepest spot of the pool.
+    pub max_depth: u64,
+    /// Amount of water held by the pool.
+    pub volume: u64,
+}
+
+/// Find all pools of water trapped in the terrain.
+/// # Arguments
+/// * `height` - A slice of integers representing the elevation of the terrain.
+/// # Returns
+/// The pools ordered from left to right. The volumes of the pools sum up
+/// to the amount of rain trapped in the terrain.
+/// # Example
+/// ```
+/// use trap_rust::{pools, Pool};
+/// let terrain = [5, 4, 2, 6, 6, 6, 4, 5];
+/// let pools = pools(&terrain);
+/// assert_eq!(pools.len(), 2);
+/// assert_eq!(
+///     pools[0],
+///     Pool {
+///         start: 1,
+///         end: 3,
+///         left_wall: 0,
+///         right_wall: 3,
+///         level: 5,
+///         max_depth: 3,
+///         volume: 4,
+///     }
+/// );
+/// assert_eq!(pools[1].volume, 1);
+/// ```
+pub fn pools(height: &[i64]) -> Vec<Pool> {
+    let levels = water_levels(height);
+    let mut pools = Vec::new();
+    let mut current: Option<Pool> = None;
+
+    for (i, (&x, &level)) in height.iter().zip(levels.iter()).enumerate() {
+        // The difference always fits into a u64, even if it overflows i64.
+        let depth = level.wrapping_sub(x) as u64;
+        if depth == 0 {
+            // A dry spot closes the pool that might be open.
+            if let Some(pool) = current.take() {
+                pools.push(Pool {
+                    end: i,
+                    right_wall: i,
+                    ..pool
+                });
+            }
+            continue;
+        }
+        let pool = current.get_or_insert(Pool {
+            start: i,
+            end: i,
+            left_wall: i - 1, // the first spot of a terrain is always dry
+            right_wall: i,
+            level,
+            max_depth: 0,
+            volume: 0,
+        });
+        pool.max_depth = pool.max_depth.max(depth);
+        pool.volume += depth;
+    }
+
+    // The last spot of a terrain is always dry, so no pool is left open.
+    debug_assert!(current.is_none());
+    pools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_rain_collected;
+    use crate::test_util::TERRAINS;
+
+    #[test]
+    fn test_pools() {
+        assert_eq!(pools(&[]), vec![]);
+        assert_eq!(pools(&[1, 2, 3, 2, 1]), vec![]);
+        assert_eq!(pools(&[6, 6, 6]), vec![]);
+
+        assert_eq!(
+            pools(&[5, 4, 2, 6, 6, 6, 4, 5]),
+            vec![
+                Pool {
+                    start: 1,
+                    end: 3,
+                    left_wall: 0,
+                    right_wall: 3,
+                    level: 5,
+                    max_depth: 3,
+                    volume: 4,
+                },
+                Pool {
+                    start: 6,
+                    end: 7,
+                    left_wall: 5,
+                    right_wall: 7,
+                    level: 5,
+                    max_depth: 1,
+                    volume: 1,
+                },
+            ]
+        );
+
+        // a spot reaching the water level splits the pool
+        assert_eq!(
+            pools(&[4, 0, 4, 1, 4])
+                .iter()
+                .map(|p| (p.start, p.end, p.volume))
+                .collect::<Vec<_>>(),
+            vec![(1, 2, 4), (3, 4, 3)]
+        );
+
+        assert_eq!(
+            pools(&[0, -6, 0, -2, 8, -9, 0, 8, 9, -5])
+                .iter()
+                .map(|p| (p.left_wall, p.right_wall, p.level, p.max_depth))
+                .collect::<Vec<_>>(),
+            vec![(0, 2, 0, 6), (2, 4, 0, 2), (4, 7, 8, 17)]
+        );
+    }
+
+    #[test]
+    fn test_pools_volume() {
+        for terrain in TERRAINS {
+            assert_eq!(
+                pools(terrain).iter().map(|p| p.volume).sum::<u64>(),
+                compute_rain_collected(terrain)
+            );
+        }
+    }
+
+    #[test]
+    fn test_pools_extreme() {
+        let found = pools(&[i64::MAX, i64::MIN, i64::MAX]);
+        assert_eq!(
+            found,
+            vec![Pool {
+                start: 1,
+                end: 2,
+                left_wall: 0,
+                right_wall: 2,
+                level: i64::MAX,
+                max_depth: u64::MAX,
+                volume: u64::MAX,
+            }]
+        );
+        let found = pools(&[0, i64::MIN, 0, i64::MIN, 0]);
+        assert_eq!(
+            found.iter().map(|p| p.volume).collect::<Vec<_>>(),
+            vec![1 << 63, 1 << 63]
+        );
+    }
+}
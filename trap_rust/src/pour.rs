@@ -0,0 +1,230 @@
+//! # Pouring water
+//! Pour water onto a single spot of a terrain bounded by infinitely high
+//! walls, one unit at a time, and find where it settles.
+
+/// Pour units of water onto a spot of the terrain.
+/// # Arguments
+/// * `height` - A slice of integers representing the elevation of the terrain.
+/// * `k` - The index of the spot the water is poured onto.
+/// * `v` - The units of water poured.
+/// # Returns
+/// The elevation of the water surface, or of the terrain where there is no
+/// water, of every spot once all units have settled.
+/// # Example
+/// ```
+/// use trap_rust::pour;
+/// assert_eq!(pour(&[2, 1, 1, 2, 1, 2, 2], 3, 4), [2, 2, 2, 3, 2, 2, 2]);
+/// assert_eq!(pour(&[1, 2, 3, 4], 2, 2), [2, 3, 3, 4]);
+/// assert_eq!(pour(&[3, 1, 3], 1, 5), [4, 4, 4]);
+/// ```
+/// # Note
+/// Each unit moves left if going left it would eventually get lower,
+/// settling on the lowest spot it can reach that way, the one nearest to
+/// `k` among equally low ones. Otherwise it moves right by the same rule,
+/// and else it stays on spot `k`. The ends of the terrain are walls, so no
+/// water is lost.
+///
+/// Pouring one unit at a time takes `O(n * v)`. Instead, the units are
+/// poured in batches: as long as they settle on the same flat stretch of
+/// spots, they fill it layer by layer, from the spot they reach first, up
+/// to the next higher spot around it. Each batch walks from `k` anew and
+/// takes `O(n)`. All batches but the last one cover their stretch up to
+/// its top, merging it with a spot next to it, so `O(n)` batches are
+/// needed and pouring takes `O(n^2)` in the worst case, independent of
+/// `v`. That case is met e.g. pouring onto the top of a slope, where each
+/// batch walks all the way down to fill the stretch at its bottom by one
+/// more step.
+/// # Panics
+/// Panics if `k` is out of bounds, or if a water surface doesn't fit into
+/// an `i64`.
+pub fn pour(height: &[i64], k: usize, v: u64) -> Vec<i64> {
+    assert!(
+        k < height.len(),
+        "index {} out of bounds for terrain of length {}",
+        k,
+        height.len()
+    );
+    let mut level: Vec<i128> = height.iter().map(|&x| x as i128).collect();
+    let mut water = v as i128;
+    while water > 0 {
+        let stretch = descend(&level, k, -1)
+            .or_else(|| descend(&level, k, 1))
+            .unwrap_or_else(|| plateau(&level, k));
+        water = fill(&mut level, &stretch, water);
+    }
+    level
+        .iter()
+        .map(|&x| i64::try_from(x).expect("water surface overflows i64"))
+        .collect()
+}
+
+/// The spots of a flat stretch in the order a layer of water covers them,
+/// and the level up to which the layers keep covering them in that order,
+/// `None` if there is no limit.
+type Stretch = (Vec<usize>, Option<i128>);
+
+/// The lowest flat stretch reached from `k` by going downhill or along
+/// flats in direction `step`, if it is lower than `k`.
+fn descend(level: &[i128], k: usize, step: isize) -> Option<Stretch> {
+    let next =
+        |i: usize| i.checked_add_signed(step).filter(|&j| j < level.len());
+    let (mut i, mut best) = (k, k);
+    while let Some(j) = next(i).filter(|&j| level[j] <= level[i]) {
+        if level[j] < level[i] {
+            best = j;
+        }
+        i = j;
+    }
+    if best == k {
+        return None;
+    }
+    // Covered starting from the spot reached first, up to the lower of the
+    // spots around the stretch.
+    let (first, last) = (best as isize, i as isize);
+    let spots = (0..=(last - first) * step)
+        .map(|d| (first + d * step) as usize)
+        .collect();
+    let before = level[(first - step) as usize];
+    let top = next(i).map_or(before, |j| level[j].min(before));
+    Some((spots, Some(top)))
+}
+
+/// The flat stretch around `k`, higher spots on both of its sides. Its
+/// layers cover `k` first, then the spots left of it from right to left,
+/// then the spots right of it from left to right.
+fn plateau(level: &[i128], k: usize) -> Stretch {
+    let surface = level[k];
+    let mut a = k;
+    while a > 0 && level[a - 1] == surface {
+        a -= 1;
+    }
+    let mut b = k;
+    while b + 1 < level.len() && level[b + 1] == surface {
+        b += 1;
+    }
+    // Once the spots up to the left end are covered, a unit going left
+    // passes a spot just one higher and may get lower beyond it.
+    let left = a.checked_sub(1).map(|i| level[i] - 1);
+    let right = level.get(b + 1).copied();
+    let top = match (left, right) {
+        (Some(left), Some(right)) => Some(left.min(right)),
+        (left, right) => left.or(right),
+    };
+    if top == Some(surface) {
+        return (vec![k], Some(surface + 1));
+    }
+    let spots = std::iter::once(k)
+        .chain((a..k).rev())
+        .chain(k + 1..=b)
+        .collect();
+    (spots, top)
+}
+
+/// Pour `water` units onto the flat `stretch`, and return the water left
+/// once it is covered up to its top.
+fn fill(level: &mut [i128], (spots, top): &Stretch, water: i128) -> i128 {
+    let surface = level[spots[0]];
+    let width = spots.len() as i128;
+    if let Some(top) = *top {
+        let needed = (top - surface) * width;
+        if water >= needed {
+            spots.iter().for_each(|&i| level[i] = top);
+            return water - needed;
+        }
+    }
+    let (layers, rest) = (water / width, (water % width) as usize);
+    for (n, &i) in spots.iter().enumerate() {
+        level[i] = surface + layers + (n < rest) as i128;
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random, TERRAINS};
+
+    /// Pour one unit at a time, `O(n * v)`.
+    fn pour_reference(height: &[i64], k: usize, v: u64) -> Vec<i64> {
+        let mut height = height.to_vec();
+        let n = height.len() as isize;
+        'units: for _ in 0..v {
+            for step in [-1, 1] {
+                let (mut i, mut best) = (k as isize, k as isize);
+                while (0..n).contains(&(i + step))
+                    && height[(i + step) as usize] <= height[i as usize]
+                {
+                    if height[(i + step) as usize] < height[i as usize] {
+                        best = i + step;
+                    }
+                    i += step;
+                }
+                if best != k as isize {
+                    height[best as usize] += 1;
+                    continue 'units;
+                }
+            }
+            height[k] += 1;
+        }
+        height
+    }
+
+    #[test]
+    fn test_pour() {
+        let flats: &[i64] = &[2, 1, 1, 2, 1, 2, 2];
+        for terrain in TERRAINS.into_iter().chain([flats]) {
+            for k in 0..terrain.len() {
+                for v in [0, 1, 2, 3, 5, 10, 40] {
+                    assert_eq!(
+                        pour(terrain, k, v),
+                        pour_reference(terrain, k, v),
+                        "{} units at {} on {:?}",
+                        v,
+                        k,
+                        terrain
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_pour_random() {
+        let mut state = 0x510E527FADE682D1u64;
+        for _ in 0..1000 {
+            let n = random(&mut state) as usize % 30 + 1;
+            let terrain: Vec<i64> =
+                (0..n).map(|_| (random(&mut state) % 8) as i64).collect();
+            let k = random(&mut state) as usize % n;
+            let v = random(&mut state) % 2000;
+            assert_eq!(
+                pour(&terrain, k, v),
+                pour_reference(&terrain, k, v),
+                "{} units at {} on {:?}",
+                v,
+                k,
+                terrain
+            );
+        }
+    }
+
+    #[test]
+    fn test_pour_large_volume() {
+        let terrain = [1_000_000_000, 3, 0, 5, 0, 1, 1_000_000_000];
+        let v = 1_000_000_000_000;
+        let result = pour(&terrain, 3, v);
+        let total: i64 = terrain.iter().sum();
+        assert_eq!(result.iter().sum::<i64>(), total + v as i64);
+        // the water rises evenly, the first spots of a layer a unit higher
+        assert!(
+            result.iter().max().unwrap() - result.iter().min().unwrap() <= 1
+        );
+        assert_eq!(pour(&terrain, 3, 20), pour_reference(&terrain, 3, 20));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_pour_out_of_bounds() {
+        pour(&[1, 2, 3], 3, 1);
+    }
+}
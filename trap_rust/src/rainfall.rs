@@ -0,0 +1,524 @@
+//! # Finite rainfall
+//! Instead of filling the terrain to capacity, let a given amount of rain
+//! fall on every spot and find where the water comes to rest.
+
+use crate::index::TerrainIndex;
+
+/// Simulate a finite amount of rain falling on the terrain.
+/// # Arguments
+/// * `height` - A slice of integers representing the elevation of the terrain.
+/// * `rain_per_cell` - The units of rain falling on each spot of the terrain.
+/// # Returns
+/// The depth of the water resting on each spot once the rain has settled.
+/// # Example
+/// ```
+/// use trap_rust::simulate_rain;
+/// let terrain = [5, 0, 3, 0, 9];
+/// // the left basin fills up and spills into the right one
+/// assert_eq!(simulate_rain(&terrain, &[0, 4, 0, 0, 0]), [0, 3, 0, 1, 0]);
+/// // both basins fill up to the rim between them and form a single lake
+/// assert_eq!(simulate_rain(&terrain, &[0, 9, 0, 0, 0]), [0, 4, 1, 4, 0]);
+/// // the lake spills over the lower rim off the left end of the terrain
+/// assert_eq!(simulate_rain(&terrain, &[0, 99, 0, 0, 0]), [0, 5, 2, 5, 0]);
+/// ```
+/// # Note
+/// Rain falling on a spot flows downhill to the lower of its neighbours,
+/// the left one if both are as high, into the basin below. A basin that is
+/// full spills over its lower rim into the neighbouring basin, and two
+/// basins filled up to the rim between them form a single lake. Water
+/// flowing over an end of the terrain is lost. With enough rain, the depths
+/// are those of [`water_depths`](crate::water_depths).
+///
+/// The terrain is split recursively at its maximum elevation. A part
+/// between two walls either holds enough water to fill up to its lower
+/// wall, or none of it spills and the water is split between the parts
+/// left and right of its own maximum, so the total amount of water
+/// entering each part is all that is needed to find its depths. A lake
+/// rising above the maximum of a part has a surface one unit higher on the
+/// spots on its left if its water doesn't fill a whole layer.
+/// # Panics
+/// Panics if `rain_per_cell` isn't as long as the terrain, or if a depth
+/// doesn't fit into an `i64`.
+pub fn simulate_rain(height: &[i64], rain_per_cell: &[u64]) -> Vec<i64> {
+    assert_eq!(
+        height.len(),
+        rain_per_cell.len(),
+        "rain_per_cell must have one entry per spot of the terrain"
+    );
+    let n = height.len();
+    let mut levels: Vec<Option<i128>> = vec![None; n];
+    if n > 0 {
+        Rainfall::new(height, rain_per_cell).run(&mut levels);
+    }
+    levels
+        .iter()
+        .zip(height)
+        .map(|(level, &h)| {
+            let depth = level.map_or(0, |level| level - h as i128);
+            i64::try_from(depth).expect("water depth overflows i64")
+        })
+        .collect()
+}
+
+/// A part of the terrain still to be resolved, with the water flowing into
+/// it over its left and right end.
+#[derive(Debug, Clone, Copy)]
+enum Part {
+    /// Spots `l..r` between two walls.
+    Basin(usize, usize, i128, i128),
+    /// Spots `0..r`, draining off the left end of the terrain.
+    LeftOpen(usize, i128),
+    /// Spots `l..n`, draining off the right end of the terrain.
+    RightOpen(usize, i128),
+    /// The whole terrain.
+    Open,
+}
+
+struct Rainfall<'a> {
+    height: &'a [i64],
+    index: TerrainIndex,
+    // prefix sums of the elevations and of the rain, sum[i] for spots 0..i
+    height_sum: Vec<i128>,
+    rain_sum: Vec<i128>,
+}
+
+impl<'a> Rainfall<'a> {
+    fn new(height: &'a [i64], rain_per_cell: &[u64]) -> Self {
+        Rainfall {
+            height,
+            index: TerrainIndex::new(height),
+            height_sum: prefix_sum(height.iter().map(|&x| x as i128)),
+            rain_sum: prefix_sum(rain_per_cell.iter().map(|&x| x as i128)),
+        }
+    }
+
+    fn run(&self, levels: &mut [Option<i128>]) {
+        let mut parts = vec![Part::Open];
+        while let Some(part) = parts.pop() {
+            match part {
+                Part::Basin(l, r, left, right) => {
+                    self.basin(l, r, left, right, levels, &mut parts)
+                }
+                Part::LeftOpen(r, right) => {
+                    // The leftmost maximum is higher than all spots left of
+                    // it, so the basin on its right spills over it.
+                    let k = self.index.maxima_in(0, r).0;
+                    let (to_left, to_right) = self.split_rain(k);
+                    let inflow = right + to_right;
+                    let spill = (self.rain(k + 1, r) + inflow
+                        - self.capacity(k + 1, r, self.height[k]))
+                    .max(0);
+                    parts.push(Part::Basin(k + 1, r, to_right, right));
+                    if k > 0 {
+                        parts.push(Part::LeftOpen(k, to_left + spill));
+                    }
+                }
+                Part::RightOpen(l, left) => {
+                    let n = self.height.len();
+                    let k = self.index.maxima_in(l, n).1;
+                    let (to_left, to_right) = self.split_rain(k);
+                    let inflow = left + to_left;
+                    let spill = (self.rain(l, k) + inflow
+                        - self.capacity(l, k, self.height[k]))
+                    .max(0);
+                    parts.push(Part::Basin(l, k, left, to_left));
+                    if k + 1 < n {
+                        parts.push(Part::RightOpen(k + 1, to_right + spill));
+                    }
+                }
+                Part::Open => {
+                    let n = self.height.len();
+                    let k = self.index.maxima_in(0, n).0;
+                    let (to_left, to_right) = self.split_rain(k);
+                    if k > 0 {
+                        parts.push(Part::LeftOpen(k, to_left));
+                    }
+                    if k + 1 < n {
+                        parts.push(Part::RightOpen(k + 1, to_right));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Resolve the spots `l..r` between the walls at `l - 1` and `r`, all
+    /// spots in between being at most as high as the walls.
+    fn basin(
+        &self,
+        l: usize,
+        r: usize,
+        left: i128,
+        right: i128,
+        levels: &mut [Option<i128>],
+        parts: &mut Vec<Part>,
+    ) {
+        if l == r {
+            return;
+        }
+        let rim = self.height[l - 1].min(self.height[r]) as i128;
+        if self.rain(l, r) + left + right >= self.capacity(l, r, rim as i64) {
+            fill(&mut levels[l..r], rim);
+            return;
+        }
+
+        let k = self.index.maxima_in(l, r).0;
+        let c = self.height[k];
+        let (to_left, to_right) = self.split_rain(k);
+        let water_left = self.rain(l, k) + left + to_left;
+        let water_right = self.rain(k + 1, r) + to_right + right;
+        let capacity_left = self.capacity(l, k, c);
+        let capacity_right = self.capacity(k + 1, r, c);
+
+        if water_left + water_right >= capacity_left + capacity_right {
+            // A single lake above the maximum. It doesn't reach the rim.
+            let width = (r - l) as i128;
+            let extra =
+                water_left + water_right - capacity_left - capacity_right;
+            let (layers, rest) = (extra / width, (extra % width) as usize);
+            fill(&mut levels[l..l + rest], c as i128 + layers + 1);
+            fill(&mut levels[l + rest..r], c as i128 + layers);
+        } else if water_left > capacity_left {
+            fill(&mut levels[l..k], c as i128);
+            let spill = water_left - capacity_left;
+            parts.push(Part::Basin(k + 1, r, to_right + spill, right));
+        } else if water_right > capacity_right {
+            fill(&mut levels[k + 1..r], c as i128);
+            let spill = water_right - capacity_right;
+            parts.push(Part::Basin(l, k, left, to_left + spill));
+        } else {
+            parts.push(Part::Basin(l, k, left, to_left));
+            parts.push(Part::Basin(k + 1, r, to_right, right));
+        }
+    }
+
+    /// The rain falling on the spots `l..r`.
+    fn rain(&self, l: usize, r: usize) -> i128 {
+        self.rain_sum[r] - self.rain_sum[l]
+    }
+
+    /// The water needed to fill the spots `l..r`, all at most `level` high,
+    /// up to `level`.
+    fn capacity(&self, l: usize, r: usize, level: i64) -> i128 {
+        level as i128 * (r - l) as i128
+            - (self.height_sum[r] - self.height_sum[l])
+    }
+
+    /// The rain falling on spot `k` flowing to its left and to its right.
+    fn split_rain(&self, k: usize) -> (i128, i128) {
+        let rain = self.rain(k, k + 1);
+        if flows_left(self.height, k) {
+            (rain, 0)
+        } else {
+            (0, rain)
+        }
+    }
+}
+
+/// Whether the rain falling on spot `k` flows to its left, to the lower of
+/// its neighbours, the left one if both are as high.
+pub(crate) fn flows_left(height: &[i64], k: usize) -> bool {
+    let left = k.checked_sub(1).map(|i| height[i]);
+    let right = height.get(k + 1).copied();
+    // None, off the end of the terrain, is lower than any spot
+    left <= right
+}
+
+fn prefix_sum(values: impl Iterator<Item = i128>) -> Vec<i128> {
+    let mut sum = vec![0];
+    for x in values {
+        sum.push(sum.last().unwrap() + x);
+    }
+    sum
+}
+
+fn fill(levels: &mut [Option<i128>], level: i128) {
+    levels.iter_mut().for_each(|l| *l = Some(level));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random, TERRAINS};
+    use crate::{compute_rain_collected, water_levels};
+
+    fn saturated_depths(terrain: &[i64]) -> Vec<i64> {
+        water_levels(terrain)
+            .iter()
+            .zip(terrain)
+            .map(|(level, h)| level - h)
+            .collect()
+    }
+
+    /// A water surface, the fraction `numerator / denominator`.
+    #[derive(Debug, Clone, Copy)]
+    struct Level(i128, i128);
+
+    impl Level {
+        fn new(numerator: i128, denominator: i128) -> Self {
+            let (mut a, mut b) = (numerator.abs(), denominator);
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            Level(numerator / a.max(1), denominator / a.max(1))
+        }
+
+        fn add(self, other: Level) -> Level {
+            Level::new(self.0 * other.1 + other.0 * self.1, self.1 * other.1)
+        }
+
+        fn sub(self, other: Level) -> Level {
+            self.add(Level(-other.0, other.1))
+        }
+
+        fn scale(self, numerator: i128, denominator: i128) -> Level {
+            Level::new(self.0 * numerator, self.1 * denominator)
+        }
+    }
+
+    impl PartialEq for Level {
+        fn eq(&self, other: &Level) -> bool {
+            self.0 * other.1 == other.0 * self.1
+        }
+    }
+
+    impl PartialOrd for Level {
+        fn partial_cmp(&self, other: &Level) -> Option<std::cmp::Ordering> {
+            (self.0 * other.1).partial_cmp(&(other.0 * self.1))
+        }
+    }
+
+    /// Let the rain fall unit by unit, `O(n)` per unit and lake it fills.
+    /// A unit follows the terrain downhill to the lower neighbour, the left
+    /// one if both are as high, and then the water surface down to the
+    /// lowest surface it reaches. There it raises the lake evenly, spilling
+    /// over the lower rim once the lake is full, or it flows off an end of
+    /// the terrain. The depths are finally rounded the way of
+    /// `simulate_rain`, a lake not filling a whole layer being one unit
+    /// higher on its left.
+    fn reference(height: &[i64], rain_per_cell: &[u64]) -> Vec<i64> {
+        let n = height.len();
+        let mut surface: Vec<Level> =
+            height.iter().map(|&h| Level(h as i128, 1)).collect();
+        for (k, &units) in rain_per_cell.iter().enumerate() {
+            for _ in 0..units {
+                if let Some(p) = downhill(height, k) {
+                    pour_unit(&mut surface, p);
+                }
+            }
+        }
+
+        let mut depths = vec![0; n];
+        let mut l = 0;
+        while l < n {
+            // the lake at l, or the dry spot l
+            let level = surface[l];
+            let r = (l..n)
+                .find(|&i| {
+                    surface[i] != level
+                        || surface[i] == Level(height[i] as i128, 1)
+                })
+                .unwrap_or(n)
+                .max(l + 1);
+            let width = (r - l) as i128;
+            let total = level.scale(width, 1);
+            assert_eq!(total.1, 1, "lakes hold whole units");
+            let rest = total.0.rem_euclid(width) as usize;
+            for i in l..r {
+                let top = total.0.div_euclid(width) + (i < l + rest) as i128;
+                depths[i] = (top - height[i] as i128) as i64;
+            }
+            l = r;
+        }
+        depths
+    }
+
+    /// Follow `spots` downhill from spot `p` to the first spot no neighbour
+    /// is lower than, `None` off an end.
+    fn downhill<T: PartialOrd + Copy>(
+        spots: &[T],
+        mut p: usize,
+    ) -> Option<usize> {
+        loop {
+            let left = p.checked_sub(1).map(|i| spots[i]);
+            let right = spots.get(p + 1).copied();
+            // None, off the end of the terrain, is lower than any spot
+            let (next, lower) = if left <= right {
+                (p.checked_sub(1), left)
+            } else {
+                (right.map(|_| p + 1), right)
+            };
+            if lower >= Some(spots[p]) {
+                return Some(p);
+            }
+            p = next?;
+        }
+    }
+
+    /// Pour a unit of water onto the surface at spot `p`.
+    fn pour_unit(surface: &mut [Level], mut p: usize) {
+        let mut water = Level(1, 1);
+        while let Some(q) = downhill(surface, p) {
+            // the lake at q, spots of the same surface next to each other
+            let level = surface[q];
+            let l = (0..q)
+                .rev()
+                .find(|&i| surface[i] != level)
+                .map_or(0, |i| i + 1);
+            let Some(r) = (q..surface.len()).find(|&i| surface[i] != level)
+            else {
+                return;
+            };
+            if l == 0 {
+                return;
+            }
+            if surface[l - 1] < level {
+                p = l - 1;
+                continue;
+            }
+            if surface[r] < level {
+                p = r;
+                continue;
+            }
+            let rim = if surface[l - 1] < surface[r] {
+                surface[l - 1]
+            } else {
+                surface[r]
+            };
+            let width = (r - l) as i128;
+            let capacity = rim.sub(level).scale(width, 1);
+            if water < capacity {
+                surface[l..r].fill(level.add(water.scale(1, width)));
+                return;
+            }
+            surface[l..r].fill(rim);
+            water = water.sub(capacity);
+            if water == Level(0, 1) {
+                return;
+            }
+            p = l;
+        }
+    }
+
+    #[test]
+    fn test_simulate_rain_saturated() {
+        for terrain in TERRAINS {
+            let rain = vec![100; terrain.len()];
+            let depths = simulate_rain(terrain, &rain);
+            assert_eq!(depths, saturated_depths(terrain), "{:?}", terrain);
+            assert_eq!(
+                depths.iter().sum::<i64>() as u64,
+                compute_rain_collected(terrain)
+            );
+        }
+    }
+
+    #[test]
+    fn test_simulate_rain() {
+        // no rain
+        assert_eq!(simulate_rain(&[3, 0, 3], &[0, 0, 0]), [0, 0, 0]);
+        // rain on the slopes flows into the valley
+        assert_eq!(
+            simulate_rain(&[9, 1, 0, 1, 9], &[0, 2, 0, 2, 0]),
+            [0, 1, 2, 1, 0]
+        );
+        // rain on a peak flows to the lower side
+        assert_eq!(
+            simulate_rain(&[9, 0, 5, 2, 9], &[0, 0, 4, 0, 0]),
+            [0, 4, 0, 0, 0]
+        );
+        assert_eq!(
+            simulate_rain(&[9, 2, 5, 0, 9], &[0, 0, 4, 0, 0]),
+            [0, 0, 0, 4, 0]
+        );
+        assert_eq!(
+            simulate_rain(&[9, 0, 5, 0, 9], &[0, 0, 4, 0, 0]),
+            [0, 4, 0, 0, 0]
+        );
+        // rain on an end of the terrain flows off it
+        assert_eq!(simulate_rain(&[1, 3, 0, 3], &[5, 0, 0, 0]), [0, 0, 0, 0]);
+        // a partial layer covers the left spots of a lake
+        assert_eq!(
+            simulate_rain(&[9, 1, 0, 1, 9], &[0, 1, 0, 1, 0]),
+            [0, 1, 1, 0, 0]
+        );
+        // a full basin spills over its lower rim, here off the right end
+        assert_eq!(
+            simulate_rain(&[9, 0, 5, 0, 1], &[0, 9, 0, 0, 0]),
+            [0, 5, 0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn test_simulate_rain_random() {
+        let mut state = 0xA54FF53A5F1D36F1u64;
+        for _ in 0..500 {
+            let n = random(&mut state) as usize % 40 + 2;
+            let terrain: Vec<i64> =
+                (0..n).map(|_| (random(&mut state) % 10) as i64).collect();
+            let rain: Vec<u64> =
+                (0..n).map(|_| random(&mut state) % 4).collect();
+            let depths = simulate_rain(&terrain, &rain);
+            let saturated = saturated_depths(&terrain);
+            assert!(depths.iter().zip(&saturated).all(|(d, s)| d <= s));
+            assert!(depths.iter().sum::<i64>() as u64 <= rain.iter().sum());
+
+            // Walled in, none of the rain is lost.
+            let mut walled = terrain.clone();
+            walled.insert(0, 1000);
+            walled.push(1000);
+            let mut rain = rain.clone();
+            rain.insert(0, 0);
+            rain.push(0);
+            let depths = simulate_rain(&walled, &rain);
+            assert_eq!(
+                depths.iter().sum::<i64>() as u64,
+                rain.iter().sum::<u64>()
+            );
+            // Water at rest is level within each lake.
+            let surface: Vec<i64> =
+                depths.iter().zip(&walled).map(|(d, h)| d + h).collect();
+            for i in 1..walled.len() {
+                if depths[i] > 0 && depths[i - 1] > 0 {
+                    assert!((surface[i] - surface[i - 1]).abs() <= 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_simulate_rain_reference() {
+        let mut state = 0xB5C0FBCFEC4D3B2Fu64;
+        for _ in 0..3000 {
+            let n = random(&mut state) as usize % 20 + 1;
+            // distinct elevations, so that a full basin has a single rim
+            let mut terrain: Vec<i64> = (0..n as i64).collect();
+            for i in (1..n).rev() {
+                terrain.swap(i, random(&mut state) as usize % (i + 1));
+            }
+            let rain: Vec<u64> =
+                (0..n).map(|_| random(&mut state) % 8).collect();
+            assert_eq!(
+                simulate_rain(&terrain, &rain),
+                reference(&terrain, &rain),
+                "{:?} {:?}",
+                terrain,
+                rain
+            );
+        }
+        for (terrain, rain) in [
+            (&[5, 0, 3, 0, 9][..], &[0, 4, 0, 0, 0][..]),
+            (&[5, 0, 3, 0, 9], &[0, 9, 0, 0, 0]),
+            (&[5, 0, 3, 0, 9], &[0, 99, 0, 0, 0]),
+            (&[9, 1, 0, 1, 9], &[0, 1, 0, 1, 0]),
+            (&[9, 0, 5, 0, 1], &[0, 9, 0, 0, 0]),
+        ] {
+            assert_eq!(simulate_rain(terrain, rain), reference(terrain, rain));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_simulate_rain_mismatched_lengths() {
+        simulate_rain(&[1, 2, 3], &[1, 2]);
+    }
+}
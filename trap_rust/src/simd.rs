@@ -0,0 +1,308 @@
+//! # SIMD computation
+//! Variant of trap computing the prefix and suffix maxima of the terrain
+//! block-wise in SIMD registers, with SSE4.2, AVX2 and AVX-512 paths chosen
+//! at runtime and a scalar fallback.
+
+/// Compute the amount of rain that can be trapped in the terrain using the
+/// widest SIMD instructions the CPU supports.
+/// # Arguments
+/// * `height` - A slice of integers representing the elevation of the terrain.
+/// # Returns
+/// The amount of rain that can be trapped in the terrain.
+/// # Example
+/// ```
+/// use trap_rust::trap_simd;
+/// let terrain = [1, 4, 2, 5, 3, 6, 4, 7];
+/// assert_eq!(trap_simd(&terrain), 6);
+/// ```
+/// # Note
+/// The water collected on a spot is the lower of the prefix maximum `P` and
+/// the suffix maximum `S` at that spot minus its elevation. One of them
+/// always is the maximum elevation `M` of the terrain, so the amount of rain
+/// trapped is `Σ(P - h) + ΣS - n * M`. Both sums are computed by a scan over
+/// the terrain in blocks of one SIMD register, the suffix maxima scanning
+/// from the end with the lanes of each block reversed. Within a block the
+/// maxima are found in `log2(lanes)` steps of shifting the lanes and taking
+/// the maximum, then combined with the maximum of all blocks before. The
+/// sums are computed modulo 2^64, which yields the exact amount of rain
+/// trapped whenever it fits into a `u64`.
+pub fn trap_simd(height: &[i64]) -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    #[allow(unsafe_code)]
+    {
+        if is_x86_feature_detected!("avx512f") {
+            // SAFETY: is_x86_feature_detected! checked the CPU has avx512f
+            return unsafe { x86::trap_avx512(height) };
+        }
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: is_x86_feature_detected! checked the CPU has avx2
+            return unsafe { x86::trap_avx2(height) };
+        }
+        if is_x86_feature_detected!("sse4.2") {
+            // SAFETY: is_x86_feature_detected! checked the CPU has sse4.2
+            return unsafe { x86::trap_sse42(height) };
+        }
+    }
+    trap_scalar(height)
+}
+
+/// Combine the sums of the forward and backward scans.
+fn combine(forward: u64, backward: u64, max: i64, len: usize) -> u64 {
+    forward
+        .wrapping_add(backward)
+        .wrapping_sub((max as u64).wrapping_mul(len as u64))
+}
+
+/// Continue the forward scan over `height`, summing up `P - h`.
+fn forward_scalar(height: &[i64], mut max: i64, mut sum: u64) -> (u64, i64) {
+    for &x in height {
+        max = max.max(x);
+        sum = sum.wrapping_add(max.wrapping_sub(x) as u64);
+    }
+    (sum, max)
+}
+
+/// Continue the backward scan over `height`, summing up `S`.
+fn backward_scalar(height: &[i64], mut max: i64, mut sum: u64) -> u64 {
+    for &x in height.iter().rev() {
+        max = max.max(x);
+        sum = sum.wrapping_add(max as u64);
+    }
+    sum
+}
+
+fn trap_scalar(height: &[i64]) -> u64 {
+    let (forward, max) = forward_scalar(height, i64::MIN, 0);
+    let backward = backward_scalar(height, i64::MIN, 0);
+    combine(forward, backward, max, height.len())
+}
+
+#[cfg(target_arch = "x86_64")]
+#[allow(unsafe_code)]
+mod x86 {
+    use super::{backward_scalar, combine, forward_scalar};
+    use std::arch::x86_64::*;
+
+    /// # Safety
+    /// The CPU must support SSE4.2.
+    #[target_feature(enable = "sse4.2")]
+    pub(super) unsafe fn trap_sse42(height: &[i64]) -> u64 {
+        #[target_feature(enable = "sse4.2")]
+        fn max(a: __m128i, b: __m128i) -> __m128i {
+            _mm_blendv_epi8(b, a, _mm_cmpgt_epi64(a, b))
+        }
+
+        #[target_feature(enable = "sse4.2")]
+        fn scan(v: __m128i, min: __m128i) -> __m128i {
+            max(v, _mm_unpacklo_epi64(min, v))
+        }
+
+        let min = _mm_set1_epi64x(i64::MIN);
+
+        let blocks = height.chunks_exact(2);
+        let rest = blocks.remainder();
+        let (mut carry, mut sum) = (min, _mm_setzero_si128());
+        for block in blocks {
+            let v = unsafe { _mm_loadu_si128(block.as_ptr().cast()) };
+            let p = max(scan(v, min), carry);
+            sum = _mm_add_epi64(sum, _mm_sub_epi64(p, v));
+            carry = _mm_unpackhi_epi64(p, p);
+        }
+        let sum = (_mm_extract_epi64::<0>(sum) as u64)
+            .wrapping_add(_mm_extract_epi64::<1>(sum) as u64);
+        let (forward, max_height) =
+            forward_scalar(rest, _mm_cvtsi128_si64(carry), sum);
+
+        let blocks = height.rchunks_exact(2);
+        let rest = blocks.remainder();
+        let (mut carry, mut sum) = (min, _mm_setzero_si128());
+        for block in blocks {
+            let v = unsafe { _mm_loadu_si128(block.as_ptr().cast()) };
+            let v = _mm_shuffle_epi32::<0b01_00_11_10>(v);
+            let s = max(scan(v, min), carry);
+            sum = _mm_add_epi64(sum, s);
+            carry = _mm_unpackhi_epi64(s, s);
+        }
+        let sum = (_mm_extract_epi64::<0>(sum) as u64)
+            .wrapping_add(_mm_extract_epi64::<1>(sum) as u64);
+        let backward = backward_scalar(rest, _mm_cvtsi128_si64(carry), sum);
+
+        combine(forward, backward, max_height, height.len())
+    }
+
+    /// # Safety
+    /// The CPU must support AVX2.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn trap_avx2(height: &[i64]) -> u64 {
+        #[target_feature(enable = "avx2")]
+        fn max(a: __m256i, b: __m256i) -> __m256i {
+            _mm256_blendv_epi8(b, a, _mm256_cmpgt_epi64(a, b))
+        }
+
+        #[target_feature(enable = "avx2")]
+        fn scan(v: __m256i, min: __m256i) -> __m256i {
+            // shift the lanes up by one, then by two
+            let t = _mm256_permute4x64_epi64::<0b10_01_00_00>(v);
+            let v = max(v, _mm256_blend_epi32::<0b0000_0011>(t, min));
+            let t = _mm256_permute4x64_epi64::<0b01_00_00_00>(v);
+            max(v, _mm256_blend_epi32::<0b0000_1111>(t, min))
+        }
+
+        #[target_feature(enable = "avx2")]
+        fn horizontal_sum(v: __m256i) -> u64 {
+            let low = _mm256_castsi256_si128(v);
+            let high = _mm256_extracti128_si256::<1>(v);
+            let v = _mm_add_epi64(low, high);
+            (_mm_cvtsi128_si64(v) as u64)
+                .wrapping_add(_mm_extract_epi64::<1>(v) as u64)
+        }
+
+        let min = _mm256_set1_epi64x(i64::MIN);
+
+        let blocks = height.chunks_exact(4);
+        let rest = blocks.remainder();
+        let (mut carry, mut sum) = (min, _mm256_setzero_si256());
+        for block in blocks {
+            let v = unsafe { _mm256_loadu_si256(block.as_ptr().cast()) };
+            let p = max(scan(v, min), carry);
+            sum = _mm256_add_epi64(sum, _mm256_sub_epi64(p, v));
+            carry = _mm256_permute4x64_epi64::<0b11_11_11_11>(p);
+        }
+        let carry = _mm_cvtsi128_si64(_mm256_castsi256_si128(carry));
+        let (forward, max_height) =
+            forward_scalar(rest, carry, horizontal_sum(sum));
+
+        let blocks = height.rchunks_exact(4);
+        let rest = blocks.remainder();
+        let (mut carry, mut sum) = (min, _mm256_setzero_si256());
+        for block in blocks {
+            let v = unsafe { _mm256_loadu_si256(block.as_ptr().cast()) };
+            let v = _mm256_permute4x64_epi64::<0b00_01_10_11>(v);
+            let s = max(scan(v, min), carry);
+            sum = _mm256_add_epi64(sum, s);
+            carry = _mm256_permute4x64_epi64::<0b11_11_11_11>(s);
+        }
+        let carry = _mm_cvtsi128_si64(_mm256_castsi256_si128(carry));
+        let backward = backward_scalar(rest, carry, horizontal_sum(sum));
+
+        combine(forward, backward, max_height, height.len())
+    }
+
+    /// # Safety
+    /// The CPU must support AVX-512F.
+    #[target_feature(enable = "avx512f")]
+    pub(super) unsafe fn trap_avx512(height: &[i64]) -> u64 {
+        #[target_feature(enable = "avx512f")]
+        fn scan(v: __m512i, min: __m512i) -> __m512i {
+            // shift the lanes up by one, two and four
+            let v = _mm512_max_epi64(v, _mm512_alignr_epi64::<7>(v, min));
+            let v = _mm512_max_epi64(v, _mm512_alignr_epi64::<6>(v, min));
+            _mm512_max_epi64(v, _mm512_alignr_epi64::<4>(v, min))
+        }
+
+        let min = _mm512_set1_epi64(i64::MIN);
+        let last = _mm512_set1_epi64(7);
+        let reverse = _mm512_set_epi64(0, 1, 2, 3, 4, 5, 6, 7);
+
+        let blocks = height.chunks_exact(8);
+        let rest = blocks.remainder();
+        let (mut carry, mut sum) = (min, _mm512_setzero_si512());
+        for block in blocks {
+            let v = unsafe { _mm512_loadu_si512(block.as_ptr().cast()) };
+            let p = _mm512_max_epi64(scan(v, min), carry);
+            sum = _mm512_add_epi64(sum, _mm512_sub_epi64(p, v));
+            carry = _mm512_permutexvar_epi64(last, p);
+        }
+        let carry = _mm_cvtsi128_si64(_mm512_castsi512_si128(carry));
+        let sum = _mm512_reduce_add_epi64(sum) as u64;
+        let (forward, max_height) = forward_scalar(rest, carry, sum);
+
+        let blocks = height.rchunks_exact(8);
+        let rest = blocks.remainder();
+        let (mut carry, mut sum) = (min, _mm512_setzero_si512());
+        for block in blocks {
+            let v = unsafe { _mm512_loadu_si512(block.as_ptr().cast()) };
+            let v = _mm512_permutexvar_epi64(reverse, v);
+            let s = _mm512_max_epi64(scan(v, min), carry);
+            sum = _mm512_add_epi64(sum, s);
+            carry = _mm512_permutexvar_epi64(last, s);
+        }
+        let carry = _mm_cvtsi128_si64(_mm512_castsi512_si128(carry));
+        let sum = _mm512_reduce_add_epi64(sum) as u64;
+        let backward = backward_scalar(rest, carry, sum);
+
+        combine(forward, backward, max_height, height.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random, TERRAINS};
+    use crate::trap;
+
+    type Trap = fn(&[i64]) -> u64;
+
+    /// All implementations the CPU running the tests supports.
+    fn implementations() -> Vec<(&'static str, Trap)> {
+        let mut implementations: Vec<(&'static str, Trap)> =
+            vec![("scalar", trap_scalar), ("dispatch", trap_simd)];
+        #[cfg(target_arch = "x86_64")]
+        #[allow(unsafe_code)]
+        {
+            // SAFETY: each function is only pushed after
+            // is_x86_feature_detected! checked the CPU has its feature
+            if is_x86_feature_detected!("sse4.2") {
+                implementations
+                    .push(("sse4.2", |h| unsafe { x86::trap_sse42(h) }));
+            }
+            if is_x86_feature_detected!("avx2") {
+                implementations
+                    .push(("avx2", |h| unsafe { x86::trap_avx2(h) }));
+            }
+            if is_x86_feature_detected!("avx512f") {
+                implementations
+                    .push(("avx512f", |h| unsafe { x86::trap_avx512(h) }));
+            }
+        }
+        implementations
+    }
+
+    #[test]
+    fn test_trap_simd() {
+        for (name, implementation) in implementations() {
+            for terrain in TERRAINS {
+                assert_eq!(
+                    implementation(terrain),
+                    trap(terrain),
+                    "{} on {:?}",
+                    name,
+                    terrain
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_trap_simd_random() {
+        let mut state = 0xB5297A4D3F2C1E07u64;
+        for n in (0..70).chain([1000, 1023, 4097]) {
+            for range in [3u64, 1000, 1 << 48] {
+                let terrain: Vec<i64> = (0..n)
+                    .map(|_| {
+                        (random(&mut state) % range) as i64 - (range / 2) as i64
+                    })
+                    .collect();
+                for (name, implementation) in implementations() {
+                    assert_eq!(
+                        implementation(&terrain),
+                        trap(&terrain),
+                        "{} on {:?}",
+                        name,
+                        terrain
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! # Streaming terrains
+//! Compute the amount of rain trapped in a terrain that arrives one spot at
+//! a time, from left to right, without keeping the whole terrain in memory.
+
+/// Accumulates the rain trapped in a terrain fed one spot at a time.
+///
+/// The accumulator keeps a monotone stack of the walls that might still hold
+/// water once a higher spot arrives on their right. Spots of equal height
+/// next to each other are merged into a single entry, so the memory needed
+/// is bounded by the number of distinct elevations seen, not by the length
+/// of the terrain.
+/// # Example
+/// ```
+/// use trap_rust::RainAccumulator;
+/// let mut rain = RainAccumulator::new();
+/// for x in [1, 4, 2, 5, 3] {
+///     rain.push(x);
+/// }
+/// assert_eq!(rain.water_so_far(), 2);
+/// rain.extend([6, 4, 7]);
+/// assert_eq!(rain.finish(), 6);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RainAccumulator {
+    walls: Vec<(i64, u64)>, // elevation and number of spots, decreasing
+    water: u64,
+}
+
+impl RainAccumulator {
+    /// Create an accumulator for an empty terrain.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the next spot of the terrain on the right.
+    pub fn push(&mut self, h: i64) {
+        // The spots lower than h between the nearest higher wall on the left
+        // and h are filled up. Filled spots are as high as their water
+        // surface and are merged with the wall they are now level with.
+        let mut width = 0u64;
+        while let Some(&(bottom, spots)) = self.walls.last() {
+            if bottom > h {
+                break;
+            }
+            self.walls.pop();
+            width += spots;
+            match self.walls.last() {
+                Some(&(left, _)) => {
+                    let level = left.min(h);
+                    // The difference always fits into a u64, even if it
+                    // overflows i64.
+                    self.water += level.wrapping_sub(bottom) as u64 * width;
+                }
+                // Water drains off the left end of the terrain.
+                None => width = 0,
+            }
+        }
+        self.walls.push((h, width + 1));
+    }
+
+    /// The amount of rain trapped in the spots seen so far, which no spot
+    /// that is yet to come can let drain.
+    pub fn water_so_far(&self) -> u64 {
+        self.water
+    }
+
+    /// The amount of rain trapped in the terrain, once all spots have been
+    /// pushed.
+    pub fn finish(self) -> u64 {
+        self.water
+    }
+}
+
+impl Extend<i64> for RainAccumulator {
+    fn extend<I: IntoIterator<Item = i64>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|h| self.push(h));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_rain_collected;
+    use crate::test_util::TERRAINS;
+
+    #[test]
+    fn test_rain_accumulator() {
+        for terrain in TERRAINS {
+            let mut rain = RainAccumulator::new();
+            for (i, &h) in terrain.iter().enumerate() {
+                rain.push(h);
+                // Water definitively trapped is the water held by the
+                // prefix on its own.
+                assert_eq!(
+                    rain.water_so_far(),
+                    compute_rain_collected(&terrain[..=i])
+                );
+            }
+            assert_eq!(rain.finish(), compute_rain_collected(terrain));
+        }
+    }
+
+    #[test]
+    fn test_rain_accumulator_merges_flats() {
+        let mut rain = RainAccumulator::new();
+        rain.extend([9, 3, 3, 3, 1, 1, 3, 3, 5]);
+        assert_eq!(rain.walls.len(), 2);
+        assert_eq!(rain.water_so_far(), 5 * 2 + 2 * 4);
+        rain.extend(std::iter::repeat_n(5, 1000));
+        assert_eq!(rain.walls, vec![(9, 1), (5, 1008)]);
+    }
+
+    #[test]
+    fn test_rain_accumulator_extreme() {
+        let terrains: [&[i64]; 4] = [
+            &[i64::MAX, i64::MIN, i64::MAX],
+            &[i64::MIN, i64::MAX, i64::MIN, i64::MAX, i64::MIN],
+            &[0, i64::MAX, -1, i64::MAX, 0],
+            &[i64::MAX, 3, -2, i64::MAX],
+        ];
+        for terrain in terrains {
+            let mut rain = RainAccumulator::new();
+            rain.extend(terrain.iter().copied());
+            assert_eq!(rain.finish(), compute_rain_collected(terrain));
+        }
+        let mut rain = RainAccumulator::new();
+        rain.extend([i64::MAX, i64::MIN, i64::MAX]);
+        assert_eq!(rain.finish(), u64::MAX);
+    }
+}
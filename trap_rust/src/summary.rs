@@ -0,0 +1,389 @@
+//! # Chunk summaries
+//! Summarize consecutive chunks of a terrain independently, e.g. on
+//! different machines, and merge the summaries into the amount of rain
+//! trapped in the whole terrain.
+
+use std::fmt;
+
+/// A summary of a chunk of a terrain that can be merged with the summary of
+/// the chunk right after it.
+///
+/// The water collected on a spot is the lower of the prefix maximum `P` and
+/// the suffix maximum `S` at that spot minus its elevation. Putting another
+/// chunk on the right of a chunk can only raise the suffix maxima of its
+/// spots, and only those lower than the maximum of the other chunk. So the
+/// summary keeps the amount of rain the chunk traps on its own and its
+/// prefix and suffix maxima, with spots of equal maxima next to each other
+/// merged into a single entry. The memory needed is bounded by the number
+/// of distinct elevations of the chunk, and merging takes time linear in
+/// the size of the summaries.
+///
+/// Merging is associative and the summary of an empty chunk is neutral, so
+/// the summaries of consecutive chunks can be merged in any grouping as long
+/// as their order is kept.
+/// # Example
+/// ```
+/// use trap_rust::{compute_rain_collected, ChunkSummary};
+/// let terrain = [0, 1, -1, 2, 1, 0, 1, 3, 2, 1, 2, 1];
+/// let left = ChunkSummary::new(&terrain[..5]);
+/// let right = ChunkSummary::new(&terrain[5..]);
+/// assert_eq!(left.water(), 2);
+/// assert_eq!(right.water(), 1);
+/// assert_eq!(left.merge(&right).water(), compute_rain_collected(&terrain));
+/// ```
+/// Summaries are sent between machines with [`ChunkSummary::to_bytes`] and
+/// [`ChunkSummary::from_bytes`].
+/// ```
+/// use trap_rust::ChunkSummary;
+/// let terrain = [0, 1, -1, 2, 1, 0, 1, 3, 2, 1, 2, 1];
+/// let bytes = ChunkSummary::new(&terrain[5..]).to_bytes();
+/// let right = ChunkSummary::from_bytes(&bytes).unwrap();
+/// assert_eq!(ChunkSummary::new(&terrain[..5]).merge(&right).water(), 7);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkSummary {
+    len: u64,
+    water: u64,
+    prefix: Vec<(i64, u64)>, // prefix maxima and number of spots, increasing
+    suffix: Vec<(i64, u64)>, // suffix maxima and number of spots, decreasing
+}
+
+impl ChunkSummary {
+    /// Summarize a chunk of a terrain.
+    /// # Arguments
+    /// * `height` - A slice of integers representing the elevation of the
+    ///   chunk.
+    pub fn new(height: &[i64]) -> Self {
+        let mut prefix = Vec::new();
+        let mut max = i64::MIN;
+        for &x in height {
+            max = max.max(x);
+            push_run(&mut prefix, max, 1);
+        }
+
+        let mut suffix = Vec::new();
+        let mut max = i64::MIN;
+        for &x in height.iter().rev() {
+            max = max.max(x);
+            push_run(&mut suffix, max, 1);
+        }
+        suffix.reverse();
+
+        ChunkSummary {
+            len: height.len() as u64,
+            water: crate::compute_rain_collected(height),
+            prefix,
+            suffix,
+        }
+    }
+
+    /// The number of spots of the chunk.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the chunk has no spots.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The amount of rain trapped in the chunk on its own.
+    pub fn water(&self) -> u64 {
+        self.water
+    }
+
+    /// Summarize the chunk followed by the chunk summarized by `other`.
+    pub fn merge(&self, other: &Self) -> Self {
+        let (max, other_max) = (self.max(), other.max());
+        let level = max.min(other_max);
+
+        // Spots of this chunk right of its maximum and spots of the other
+        // chunk left of its maximum are filled up to the lower maximum.
+        let fill = |runs: &[(i64, u64)]| -> u64 {
+            runs.iter()
+                .filter(|&&(m, _)| m < level)
+                .map(|&(m, spots)| level.wrapping_sub(m) as u64 * spots)
+                .sum()
+        };
+        let water =
+            self.water + other.water + fill(&self.suffix) + fill(&other.prefix);
+
+        let mut prefix = self.prefix.clone();
+        for &(m, spots) in &other.prefix {
+            push_run(&mut prefix, m.max(max), spots);
+        }
+        let mut suffix = Vec::with_capacity(self.suffix.len());
+        for &(m, spots) in &self.suffix {
+            push_run(&mut suffix, m.max(other_max), spots);
+        }
+        for &(m, spots) in &other.suffix {
+            push_run(&mut suffix, m, spots);
+        }
+
+        ChunkSummary {
+            len: self.len + other.len,
+            water,
+            prefix,
+            suffix,
+        }
+    }
+
+    /// Encode the summary as bytes.
+    ///
+    /// The bytes are a sequence of little-endian 64-bit words: the number
+    /// of spots, the amount of rain trapped, then the number of prefix
+    /// maxima followed by the pairs of maximum and number of spots, and the
+    /// same for the suffix maxima.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut words = vec![self.len, self.water];
+        for runs in [&self.prefix, &self.suffix] {
+            words.push(runs.len() as u64);
+            words.extend(runs.iter().flat_map(|&(m, spots)| [m as u64, spots]));
+        }
+        words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    /// Decode a summary encoded by [`ChunkSummary::to_bytes`].
+    /// # Arguments
+    /// * `bytes` - The encoded summary.
+    /// # Returns
+    /// The summary, or a [`SummaryError`] if the bytes aren't a summary of
+    /// some chunk.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SummaryError> {
+        if !bytes.len().is_multiple_of(8) {
+            return Err(SummaryError::Length);
+        }
+        let mut words = bytes
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()));
+        let mut next = || words.next().ok_or(SummaryError::Length);
+        let (len, water) = (next()?, next()?);
+        let mut runs = || -> Result<Vec<(i64, u64)>, SummaryError> {
+            (0..next()?)
+                .map(|_| Ok((next()? as i64, next()?)))
+                .collect()
+        };
+        let (prefix, suffix) = (runs()?, runs()?);
+        if next().is_ok() {
+            return Err(SummaryError::Length);
+        }
+
+        let summary = ChunkSummary {
+            len,
+            water,
+            prefix,
+            suffix,
+        };
+        if summary.is_valid() {
+            Ok(summary)
+        } else {
+            Err(SummaryError::Invalid)
+        }
+    }
+
+    /// Whether the maxima are monotonic, cover all spots and meet at the
+    /// maximum of the chunk.
+    fn is_valid(&self) -> bool {
+        let spots = |runs: &[(i64, u64)]| {
+            runs.iter().try_fold(0u64, |n, &(_, spots)| {
+                (spots > 0).then(|| n.checked_add(spots)).flatten()
+            })
+        };
+        spots(&self.prefix) == Some(self.len)
+            && spots(&self.suffix) == Some(self.len)
+            && self.prefix.windows(2).all(|w| w[0].0 < w[1].0)
+            && self.suffix.windows(2).all(|w| w[0].0 > w[1].0)
+            && self.prefix.last().map(|&(m, _)| m)
+                == self.suffix.first().map(|&(m, _)| m)
+    }
+
+    fn max(&self) -> i64 {
+        self.prefix.last().map_or(i64::MIN, |&(m, _)| m)
+    }
+}
+
+/// Error returned by [`ChunkSummary::from_bytes`] for bytes that aren't a
+/// summary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SummaryError {
+    /// The bytes end before the summary does, or go on after it.
+    Length,
+    /// The maxima aren't monotonic or don't add up to the number of spots.
+    Invalid,
+}
+
+impl fmt::Display for SummaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SummaryError::Length => {
+                write!(f, "length of bytes doesn't match the summary")
+            }
+            SummaryError::Invalid => write!(f, "bytes are no valid summary"),
+        }
+    }
+}
+
+impl std::error::Error for SummaryError {}
+
+/// Append `spots` spots of maximum `m`, merging them with the last entry if
+/// that has the same maximum.
+fn push_run(runs: &mut Vec<(i64, u64)>, m: i64, spots: u64) {
+    match runs.last_mut() {
+        Some((last, count)) if *last == m => *count += spots,
+        _ => runs.push((m, spots)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_rain_collected;
+    use crate::test_util::{random, TERRAINS};
+
+    #[test]
+    fn test_merge() {
+        for terrain in TERRAINS {
+            for split in 0..=terrain.len() {
+                let (left, right) = terrain.split_at(split);
+                let merged =
+                    ChunkSummary::new(left).merge(&ChunkSummary::new(right));
+                assert_eq!(merged, ChunkSummary::new(terrain));
+                assert_eq!(merged.water(), compute_rain_collected(terrain));
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_random_splits() {
+        let mut state = 0x6A09E667F3BCC909u64;
+        for _ in 0..200 {
+            let n = random(&mut state) as usize % 200;
+            let range = [3, 10, 1000][random(&mut state) as usize % 3];
+            let terrain: Vec<i64> = (0..n)
+                .map(|_| (random(&mut state) % range) as i64 - range as i64 / 2)
+                .collect();
+
+            let mut splits: Vec<usize> = (0..random(&mut state) % 8)
+                .map(|_| random(&mut state) as usize % (n + 1))
+                .collect();
+            splits.push(0);
+            splits.push(n);
+            splits.sort_unstable();
+            let summaries: Vec<ChunkSummary> = splits
+                .windows(2)
+                .map(|w| ChunkSummary::new(&terrain[w[0]..w[1]]))
+                .collect();
+
+            let expected = ChunkSummary::new(&terrain);
+            assert_eq!(expected.water(), compute_rain_collected(&terrain));
+            assert_eq!(expected.len(), n as u64);
+            // left to right
+            let folded = summaries
+                .iter()
+                .fold(ChunkSummary::default(), |acc, s| acc.merge(s));
+            assert_eq!(folded, expected);
+            // right to left
+            let folded = summaries
+                .iter()
+                .rev()
+                .fold(ChunkSummary::default(), |acc, s| s.merge(&acc));
+            assert_eq!(folded, expected);
+            // pairwise, as a tree
+            let mut level = summaries;
+            while level.len() > 1 {
+                level = level
+                    .chunks(2)
+                    .map(|pair| match pair {
+                        [a, b] => a.merge(b),
+                        [a] => a.clone(),
+                        _ => unreachable!(),
+                    })
+                    .collect();
+            }
+            assert_eq!(level[0], expected);
+        }
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let mut state = 0x7137449123EF65CDu64;
+        for terrain in TERRAINS {
+            for split in 0..=terrain.len() {
+                let (left, right) = terrain.split_at(split);
+                let left = ChunkSummary::new(left).to_bytes();
+                let right = ChunkSummary::new(right).to_bytes();
+                let left = ChunkSummary::from_bytes(&left).unwrap();
+                let right = ChunkSummary::from_bytes(&right).unwrap();
+                assert_eq!(left.merge(&right), ChunkSummary::new(terrain));
+            }
+        }
+        for _ in 0..100 {
+            let n = random(&mut state) as usize % 100;
+            let terrain: Vec<i64> = (0..n)
+                .map(|_| (random(&mut state) % 1000) as i64 - 500)
+                .collect();
+            let summary = ChunkSummary::new(&terrain);
+            let decoded = ChunkSummary::from_bytes(&summary.to_bytes());
+            assert_eq!(decoded, Ok(summary));
+        }
+    }
+
+    #[test]
+    fn test_from_bytes_invalid() {
+        let summary = ChunkSummary::new(&[3, 1, 2, 0]);
+        let bytes = summary.to_bytes();
+        for end in 0..bytes.len() {
+            assert!(ChunkSummary::from_bytes(&bytes[..end]).is_err());
+        }
+        let mut longer = bytes.clone();
+        longer.extend(0u64.to_le_bytes());
+        assert_eq!(
+            ChunkSummary::from_bytes(&longer),
+            Err(SummaryError::Length)
+        );
+
+        let invalid = |summary: ChunkSummary| {
+            assert_eq!(
+                ChunkSummary::from_bytes(&summary.to_bytes()),
+                Err(SummaryError::Invalid)
+            );
+        };
+        // wrong number of spots
+        invalid(ChunkSummary {
+            len: 5,
+            ..summary.clone()
+        });
+        // prefix maxima decreasing
+        invalid(ChunkSummary {
+            prefix: vec![(3, 2), (2, 2)],
+            ..summary.clone()
+        });
+        // suffix maxima increasing
+        invalid(ChunkSummary {
+            suffix: vec![(0, 1), (2, 2), (3, 1)],
+            ..summary.clone()
+        });
+        // maxima of prefix and suffix differ
+        invalid(ChunkSummary {
+            prefix: vec![(4, 4)],
+            ..summary.clone()
+        });
+        // runs of no spots
+        invalid(ChunkSummary {
+            prefix: vec![(1, 0), (3, 4)],
+            ..summary
+        });
+        assert_eq!(
+            ChunkSummary::from_bytes(&ChunkSummary::default().to_bytes()),
+            Ok(ChunkSummary::default())
+        );
+    }
+
+    #[test]
+    fn test_merge_extreme() {
+        let terrain = [i64::MIN, i64::MAX, i64::MIN, i64::MAX, 0];
+        let merged = ChunkSummary::new(&terrain[..3])
+            .merge(&ChunkSummary::new(&terrain[3..]));
+        assert_eq!(merged.water(), u64::MAX);
+    }
+}
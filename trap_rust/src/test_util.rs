@@ -0,0 +1,27 @@
+//! Fixtures shared by the tests of the modules.
+
+/// The terrains every algorithm is checked on.
+pub(crate) const TERRAINS: [&[i64]; 14] = [
+    &[],
+    &[7],
+    &[2, 1],
+    &[0, 0, 0, 0, 0],
+    &[1, 2, 3, 4, 5],
+    &[5, 4, 3, 2, 1],
+    &[1, 2, 3, 2, 1],
+    &[1, 2, 3, 2, 4, 1],
+    &[1, 4, 2, 5, 3, 6, 4, 7],
+    &[2, 1, 2],
+    &[5, 4, 2, 6, 6, 6, 4, 5],
+    &[0, 1, -1, 2, 1, 0, 1, 3, 2, 1, 2, 1],
+    &[4, 2, 0, 3, 2, 5],
+    &[0, -6, 0, -2, 8, -9, 0, 8, 9, -5],
+];
+
+/// Small deterministic pseudo random generator, xorshift64.
+pub(crate) fn random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
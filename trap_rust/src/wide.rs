@@ -0,0 +1,134 @@
+//! # Wide accumulator
+//! Variant of compute_rain_collected computing the amount of rain trapped
+//! in 128 bits, which cannot overflow for any terrain of `i64` elevations
+//! that fits into memory.
+
+use crate::WaterDepths;
+
+/// Amount of rain trapped as returned by
+/// [`compute_rain_collected_wide_ffi`], split into two 64 bit words.
+/// ```c
+/// #include <stdint.h>
+/// typedef struct { uint64_t low; uint64_t high; } WideVolume;
+/// ```
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct WideVolume {
+    /// The lower 64 bits of the amount of rain trapped.
+    pub low: u64,
+    /// The upper 64 bits of the amount of rain trapped.
+    pub high: u64,
+}
+
+impl From<u128> for WideVolume {
+    fn from(water: u128) -> Self {
+        WideVolume {
+            low: water as u64,
+            high: (water >> 64) as u64,
+        }
+    }
+}
+
+impl From<WideVolume> for u128 {
+    fn from(water: WideVolume) -> Self {
+        (water.high as u128) << 64 | water.low as u128
+    }
+}
+
+/// C interface to compute_rain_collected_wide
+/// # Safety
+/// The caller must ensure that the pointer is valid and points to a valid
+/// slice of integers. If `len` is zero, the pointer may be null.
+/// # Arguments
+/// * `height` - A pointer to a slice of integers representing the elevation of the terrain.
+/// * `len` - The length of the slice.
+/// # Returns
+/// The amount of rain that can be trapped in the terrain.
+/// # Example
+/// ```
+/// use trap_rust::compute_rain_collected_wide_ffi;
+/// let terrain = [i64::MAX, i64::MIN, i64::MIN, i64::MAX];
+/// let water_capacity = unsafe {
+///     compute_rain_collected_wide_ffi(terrain.as_ptr(), terrain.len())
+/// };
+/// assert_eq!(water_capacity.high, 1);
+/// assert_eq!(water_capacity.low, u64::MAX - 1);
+/// ```
+/// # Note
+/// It is meant to be called from C code.
+/// ```c
+/// #include <stdint.h>
+/// WideVolume compute_rain_collected_wide_ffi(int64_t *height, size_t len);
+/// ```
+#[allow(unsafe_code)]
+#[no_mangle]
+pub unsafe extern "C" fn compute_rain_collected_wide_ffi(
+    height: *const i64,
+    len: usize,
+) -> WideVolume {
+    if len == 0 {
+        // height may be null for an empty terrain
+        return WideVolume::default();
+    }
+    let slice = std::slice::from_raw_parts(height, len);
+    compute_rain_collected_wide(slice).into()
+}
+
+/// Compute the amount of rain that can be trapped in the terrain using a
+/// 128 bit accumulator.
+/// # Arguments
+/// * `height` - A slice of integers representing the elevation of the terrain.
+/// # Returns
+/// The amount of rain that can be trapped in the terrain.
+/// # Example
+/// ```
+/// use trap_rust::compute_rain_collected_wide;
+/// let terrain = [i64::MAX, 0, 0, 0, i64::MAX];
+/// assert_eq!(compute_rain_collected_wide(&terrain), 3 * i64::MAX as u128);
+/// ```
+/// # Note
+/// The difference between the step of the stair and the elevation of a spot
+/// is computed in `i128`, so it never exceeds `u64::MAX`, and the total never
+/// exceeds `len * u64::MAX`.
+pub fn compute_rain_collected_wide(height: &[i64]) -> u128 {
+    let mut steps = WaterDepths::new(height);
+    let mut water = 0u128;
+    while let Some((i, stepsize)) = steps.next_step() {
+        water += (stepsize as i128 - height[i] as i128) as u128;
+    }
+    water
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_rain_collected;
+    use crate::test_util::TERRAINS;
+
+    #[test]
+    fn test_compute_rain_collected_wide() {
+        for terrain in TERRAINS {
+            assert_eq!(
+                compute_rain_collected_wide(terrain),
+                compute_rain_collected(terrain) as u128
+            );
+        }
+
+        let terrain = [i64::MAX, i64::MIN, i64::MIN, i64::MAX];
+        assert_eq!(compute_rain_collected_wide(&terrain), 2 * u64::MAX as u128);
+    }
+
+    #[test]
+    fn test_compute_rain_collected_wide_ffi() {
+        let terrain = [i64::MAX, i64::MIN, i64::MIN, i64::MIN, i64::MAX];
+        let water = unsafe {
+            compute_rain_collected_wide_ffi(terrain.as_ptr(), terrain.len())
+        };
+        assert_eq!(u128::from(water), 3 * u64::MAX as u128);
+        assert_eq!(WideVolume::from(3 * u64::MAX as u128), water);
+        assert_eq!(
+            unsafe { compute_rain_collected_wide_ffi(std::ptr::null(), 0) },
+            WideVolume::default()
+        );
+    }
+}
@@ -3,6 +3,9 @@ import Foundation
 // create a function to calculate the amount of rain collected
 @_cdecl("rainCollected") // necessary yet to provide rust interface
 public func rainCollected(_ heights: UnsafePointer<Int64>, _ count: UInt64) -> UInt64 {
+    if count < 2 {
+        return 0
+    }
     var leftMax: Int64 = Int64.min
     var rightMax: Int64 = Int64.min
     var left = 0
@@ -9,3 +9,25 @@ include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 pub fn trap_swift(terrain: &[i64]) -> u64 {
     unsafe { rainCollected(terrain.as_ptr(), terrain.len() as u64) }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trap_swift() {
+        let terrain1 = [1, 4, 2, 5, 3, 6, 4, 7];
+        assert_eq!(trap_swift(&terrain1), 6);
+
+        let terrain2 = [0, -6, 0, -2, 8, -9, 0, 8, 9, -5];
+        assert_eq!(trap_swift(&terrain2), 33);
+    }
+
+    #[test]
+    fn test_tiny_terrains() {
+        let terrains: [&[i64]; 5] = [&[], &[7], &[-3], &[1, 2], &[2, 1]];
+        for terrain in terrains {
+            assert_eq!(trap_swift(terrain), 0);
+        }
+    }
+}
@@ -46,4 +46,12 @@ mod tests {
         let terrain11 = [0, -6, 0, -2, 8, -9, 0, 8, 9, -5];
         assert_eq!(trap_zig_ffi(&terrain11), 33);
     }
+
+    #[test]
+    fn test_tiny_terrains() {
+        let terrains: [&[i64]; 5] = [&[], &[7], &[-3], &[1, 2], &[2, 1]];
+        for terrain in terrains {
+            assert_eq!(trap_zig_ffi(terrain), 0);
+        }
+    }
 }
\ No newline at end of file
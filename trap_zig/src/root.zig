@@ -1,6 +1,9 @@
 const minInt = @import("std").math.minInt;
 
 pub fn trap(terrain: []const i64) u64 {
+    if (terrain.len < 2) {
+        return 0;
+    }
     var l: usize = 0;
     var r = terrain.len - 1;
     var countWater: u64 = 0;
@@ -43,3 +46,10 @@ test "trap" {
     try expect(trap(&[_]i64{ 4, 2, 0, 3, 2, 5 }) == 9);
     try expect(trap(&[_]i64{ 0, -6, 0, -2, 8, -9, 0, 8, 9, -5 }) == 33);
 }
+
+test "trap tiny terrains" {
+    try expect(trap(&[_]i64{}) == 0);
+    try expect(trap(&[_]i64{7}) == 0);
+    try expect(trap(&[_]i64{ 1, 2 }) == 0);
+    try expect(trap(&[_]i64{ 2, 1 }) == 0);
+}